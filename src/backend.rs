@@ -0,0 +1,424 @@
+#[cfg(feature = "async")]
+use std::future::Future;
+use std::io::ErrorKind;
+#[cfg(feature = "async")]
+use std::pin::Pin;
+use std::sync::{Arc, LazyLock, RwLock};
+
+use super::os;
+use super::{Action, Attempt, Invocation, Outcome, ShutdownError, ShutdownResult, Step};
+
+/// A mechanism able to perform power actions, such as a session manager or a system service.
+///
+/// Backends are tried in the order they appear in the [`Registry`]. Implement this trait to
+/// plug in a custom session manager or a site-specific script.
+pub trait PowerBackend: Send + Sync {
+    /// Unique name of the backend, used to remove or reorder it in the [`Registry`].
+    fn name(&self) -> &str;
+
+    /// Checks whether the backend is available on this system.
+    fn probe(&self) -> bool;
+
+    /// Checks whether the backend knows how to perform `action`.
+    fn supports(&self, action: Action) -> bool;
+
+    /// Performs `action`, describing how it was carried out.
+    fn perform(&self, action: Action) -> ShutdownResult;
+
+    /// Describes the calls [`PowerBackend::perform`] would make for `action`, without making them.
+    fn invocations(&self, _action: Action) -> Vec<Invocation> {
+        Vec::new()
+    }
+
+    /// Checks whether the backend is available on this system, without blocking the calling task.
+    ///
+    /// The default implementation calls [`PowerBackend::probe`].
+    #[cfg(feature = "async")]
+    fn probe_async(&self) -> BoxFuture<'_, bool> {
+        Box::pin(async move { self.probe() })
+    }
+
+    /// Performs `action` without blocking the calling task.
+    ///
+    /// The default implementation calls [`PowerBackend::perform`], override it if that may block.
+    #[cfg(feature = "async")]
+    fn perform_async(&self, action: Action) -> BoxFuture<'_, ShutdownResult> {
+        Box::pin(async move { self.perform(action) })
+    }
+}
+
+/// A boxed future returned by the async methods of [`PowerBackend`].
+#[cfg(feature = "async")]
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// An ordered list of [`PowerBackend`]s.
+#[derive(Clone, Default)]
+pub struct Registry {
+    backends: Vec<Arc<dyn PowerBackend>>,
+}
+
+impl Registry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Registry::default()
+    }
+
+    /// Creates a registry containing the built-in backends, in their default order:
+    /// `org.gnome.SessionManager`, `org.kde.ksmserver`, `org.xfce.SessionManager`,
+    /// `org.freedesktop.login1`, `org.freedesktop.PowerManagement`,
+    /// `org.freedesktop.SessionManagement`, `org.freedesktop.ConsoleKit`,
+    /// `org.freedesktop.UPower`, `org.freedesktop.Hal`, `org.freedesktop.systemd1`,
+    /// `org.gnome.ScreenSaver`, `org.freedesktop.ScreenSaver`, `org.kde.screensaver` and `command`.
+    pub fn builtin() -> Self {
+        Registry {
+            backends: os::builtin_backends(),
+        }
+    }
+
+    /// Appends `backend` to the end of the registry.
+    pub fn register<B: PowerBackend + 'static>(&mut self, backend: B) {
+        self.backends.push(Arc::new(backend));
+    }
+
+    /// Inserts `backend` at position `index`, shifting the following backends.
+    pub fn insert<B: PowerBackend + 'static>(&mut self, index: usize, backend: B) {
+        self.backends.insert(index, Arc::new(backend));
+    }
+
+    /// Removes the backend named `name`, returning it if it was registered.
+    pub fn remove(&mut self, name: &str) -> Option<Arc<dyn PowerBackend>> {
+        let index = self.position(name)?;
+        Some(self.backends.remove(index))
+    }
+
+    /// Moves the backend named `name` to position `index`. Returns `false` if it is not registered.
+    pub fn move_to(&mut self, name: &str, index: usize) -> bool {
+        match self.position(name) {
+            Some(position) => {
+                let backend = self.backends.remove(position);
+                let index = index.min(self.backends.len());
+                self.backends.insert(index, backend);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the registered backends, in order.
+    pub fn backends(&self) -> &[Arc<dyn PowerBackend>] {
+        &self.backends
+    }
+
+    /// Tries each available backend supporting `action` in order, until one of them succeeds,
+    /// returning the outcome reported by that backend.
+    ///
+    /// Stops with [`ShutdownError::Cancelled`] as soon as a backend reports the user cancelled the request.
+    pub fn perform(&self, action: Action) -> ShutdownResult {
+        let mut attempts = Vec::new();
+        for backend in self.backends.iter().filter(|b| b.supports(action)) {
+            let result = if backend.probe() {
+                backend.perform(action)
+            } else {
+                Err(unavailable(backend.as_ref()))
+            };
+            if let Some(outcome) = record(&mut attempts, backend.as_ref(), result) {
+                return Ok(outcome);
+            }
+            if is_cancelled(&attempts) {
+                break;
+            }
+        }
+        Err(ShutdownError::from_attempts(attempts))
+    }
+
+    /// Async version of [`Registry::perform`].
+    #[cfg(feature = "async")]
+    pub async fn perform_async(&self, action: Action) -> ShutdownResult {
+        let mut attempts = Vec::new();
+        for backend in self.backends.iter().filter(|b| b.supports(action)) {
+            let result = if backend.probe_async().await {
+                backend.perform_async(action).await
+            } else {
+                Err(unavailable(backend.as_ref()))
+            };
+            if let Some(outcome) = record(&mut attempts, backend.as_ref(), result) {
+                return Ok(outcome);
+            }
+            if is_cancelled(&attempts) {
+                break;
+            }
+        }
+        Err(ShutdownError::from_attempts(attempts))
+    }
+
+    /// Lists the backends [`Registry::perform`] would try for `action`, in order, without invoking them.
+    pub fn plan(&self, action: Action) -> Vec<Step> {
+        self.backends
+            .iter()
+            .filter(|b| b.supports(action))
+            .map(|backend| Step {
+                backend: backend.name().to_string(),
+                available: backend.probe(),
+                invocations: backend.invocations(action),
+            })
+            .collect()
+    }
+
+    /// Async version of [`Registry::plan`].
+    #[cfg(feature = "async")]
+    pub async fn plan_async(&self, action: Action) -> Vec<Step> {
+        let mut steps = Vec::new();
+        for backend in self.backends.iter().filter(|b| b.supports(action)) {
+            steps.push(Step {
+                backend: backend.name().to_string(),
+                available: backend.probe_async().await,
+                invocations: backend.invocations(action),
+            });
+        }
+        steps
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.backends.iter().position(|b| b.name() == name)
+    }
+}
+
+fn unavailable(backend: &dyn PowerBackend) -> ShutdownError {
+    ShutdownError::NoBackendAvailable(vec![Attempt::new(
+        backend.name(),
+        "backend not available",
+        ErrorKind::NotFound,
+    )])
+}
+
+/// Records the failure of `backend` into `attempts`, or returns its outcome if it succeeded.
+fn record(
+    attempts: &mut Vec<Attempt>,
+    backend: &dyn PowerBackend,
+    result: ShutdownResult,
+) -> Option<Outcome> {
+    match result {
+        Ok(outcome) => return Some(outcome),
+        Err(error) if error.attempts().is_empty() => attempts.push(Attempt::new(
+            backend.name(),
+            error.to_string(),
+            error.kind(),
+        )),
+        Err(error) => attempts.extend_from_slice(error.attempts()),
+    }
+    None
+}
+
+/// Checks whether the user cancelled the request, who should not be asked again by the next backend.
+pub(crate) fn is_cancelled(attempts: &[Attempt]) -> bool {
+    attempts
+        .last()
+        .is_some_and(|a| a.kind == ErrorKind::Interrupted)
+}
+
+static REGISTRY: LazyLock<RwLock<Registry>> = LazyLock::new(|| RwLock::new(Registry::builtin()));
+
+/// Returns a copy of the registry used by the power actions of this crate.
+pub fn registry() -> Registry {
+    REGISTRY.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Replaces the registry used by the power actions of this crate.
+pub fn set_registry(registry: Registry) {
+    *REGISTRY.write().unwrap_or_else(|e| e.into_inner()) = registry;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::Status;
+
+    /// A backend recording the actions it is asked to perform into a shared log.
+    struct Mock {
+        name: &'static str,
+        available: bool,
+        action: Action,
+        /// What `perform` fails with, if it fails.
+        failure: Option<ErrorKind>,
+        log: Arc<Mutex<Vec<&'static str>>>,
+    }
+
+    impl PowerBackend for Mock {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn probe(&self) -> bool {
+            self.available
+        }
+
+        fn supports(&self, action: Action) -> bool {
+            action == self.action
+        }
+
+        fn perform(&self, _action: Action) -> ShutdownResult {
+            self.log.lock().unwrap().push(self.name);
+            match self.failure {
+                None => {
+                    let invocation = Invocation::Native(self.name.to_string());
+                    Ok(Outcome::new(self.name, invocation, Status::Completed))
+                }
+                Some(kind) => Err(ShutdownError::from_attempts(vec![Attempt::new(
+                    self.name, "failed", kind,
+                )])),
+            }
+        }
+    }
+
+    struct Fixture {
+        log: Arc<Mutex<Vec<&'static str>>>,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            Fixture {
+                log: Arc::default(),
+            }
+        }
+
+        fn backend(&self, name: &'static str, failure: Option<ErrorKind>) -> Mock {
+            Mock {
+                name,
+                available: true,
+                action: Action::Shutdown,
+                failure,
+                log: Arc::clone(&self.log),
+            }
+        }
+
+        fn calls(&self) -> Vec<&'static str> {
+            self.log.lock().unwrap().clone()
+        }
+    }
+
+    fn names(registry: &Registry) -> Vec<&str> {
+        registry.backends().iter().map(|b| b.name()).collect()
+    }
+
+    #[test]
+    fn register_insert_remove_and_move_to_keep_the_order() {
+        let fixture = Fixture::new();
+        let mut registry = Registry::new();
+        registry.register(fixture.backend("a", None));
+        registry.register(fixture.backend("b", None));
+        registry.insert(1, fixture.backend("c", None));
+        registry.insert(0, fixture.backend("d", None));
+        assert_eq!(names(&registry), ["d", "a", "c", "b"]);
+
+        assert_eq!(
+            registry.remove("c").map(|b| b.name().to_string()),
+            Some("c".into())
+        );
+        assert!(registry.remove("c").is_none());
+        assert_eq!(names(&registry), ["d", "a", "b"]);
+
+        assert!(registry.move_to("d", 2));
+        assert_eq!(names(&registry), ["a", "b", "d"]);
+        // Past the end moves the backend last
+        assert!(registry.move_to("a", 10));
+        assert_eq!(names(&registry), ["b", "d", "a"]);
+        assert!(!registry.move_to("missing", 0));
+        assert_eq!(names(&registry), ["b", "d", "a"]);
+    }
+
+    #[test]
+    fn perform_tries_the_backends_in_order_until_one_succeeds() {
+        let fixture = Fixture::new();
+        let mut registry = Registry::new();
+        registry.register(fixture.backend("failing", Some(ErrorKind::PermissionDenied)));
+        registry.register(Mock {
+            available: false,
+            ..fixture.backend("unavailable", None)
+        });
+        registry.register(Mock {
+            action: Action::Reboot,
+            ..fixture.backend("unsupported", None)
+        });
+        registry.register(fixture.backend("working", None));
+        registry.register(fixture.backend("unused", None));
+
+        let outcome = registry.perform(Action::Shutdown).unwrap();
+        assert_eq!(outcome.backend, "working");
+        assert_eq!(fixture.calls(), ["failing", "working"]);
+    }
+
+    #[test]
+    fn perform_reports_every_failure_in_order() {
+        let fixture = Fixture::new();
+        let mut registry = Registry::new();
+        registry.register(fixture.backend("denied", Some(ErrorKind::PermissionDenied)));
+        registry.register(Mock {
+            available: false,
+            ..fixture.backend("unavailable", None)
+        });
+        registry.register(fixture.backend("failed", Some(ErrorKind::Other)));
+
+        let error = registry.perform(Action::Shutdown).unwrap_err();
+        assert!(matches!(error, ShutdownError::PermissionDenied(_)));
+        let attempts = error
+            .attempts()
+            .iter()
+            .map(|a| (a.backend.as_str(), a.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            attempts,
+            [
+                ("denied", ErrorKind::PermissionDenied),
+                ("unavailable", ErrorKind::NotFound),
+                ("failed", ErrorKind::Other),
+            ]
+        );
+        assert_eq!(fixture.calls(), ["denied", "failed"]);
+    }
+
+    #[test]
+    fn perform_stops_when_the_user_cancels() {
+        let fixture = Fixture::new();
+        let mut registry = Registry::new();
+        registry.register(fixture.backend("failing", Some(ErrorKind::Other)));
+        registry.register(fixture.backend("dialog", Some(ErrorKind::Interrupted)));
+        registry.register(fixture.backend("working", None));
+
+        let error = registry.perform(Action::Shutdown).unwrap_err();
+        assert!(matches!(error, ShutdownError::Cancelled(_)));
+        assert_eq!(error.attempts().len(), 2);
+        assert_eq!(fixture.calls(), ["failing", "dialog"]);
+    }
+
+    #[test]
+    fn perform_without_backends_fails() {
+        let error = Registry::new().perform(Action::Shutdown).unwrap_err();
+        assert!(matches!(error, ShutdownError::NoBackendAvailable(_)));
+        assert!(error.attempts().is_empty());
+    }
+
+    #[test]
+    fn plan_lists_the_supporting_backends_without_performing() {
+        let fixture = Fixture::new();
+        let mut registry = Registry::new();
+        registry.register(fixture.backend("a", None));
+        registry.register(Mock {
+            action: Action::Reboot,
+            ..fixture.backend("b", None)
+        });
+        registry.register(Mock {
+            available: false,
+            ..fixture.backend("c", None)
+        });
+
+        let steps = registry.plan(Action::Shutdown);
+        let steps = steps
+            .iter()
+            .map(|s| (s.backend.as_str(), s.available))
+            .collect::<Vec<_>>();
+        assert_eq!(steps, [("a", true), ("c", false)]);
+        assert!(fixture.calls().is_empty());
+    }
+}
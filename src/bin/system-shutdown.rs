@@ -0,0 +1,668 @@
+//! Command-line tool performing the power actions of `system_shutdown`.
+
+use std::env;
+use std::fmt::Write;
+use std::process::ExitCode;
+use std::thread;
+use std::time::Duration;
+
+use system_shutdown::{Action, Outcome, ShutdownError, ShutdownResult, Status, Step};
+
+const USAGE: &str = "\
+Usage: system-shutdown [OPTIONS] <COMMAND>
+
+Commands:
+  shutdown                       Shut down the machine
+  force-shutdown                 Shut down the machine without confirmation
+  reboot                         Reboot the machine
+  force-reboot                   Reboot the machine without confirmation
+  logout                         Log out the current user
+  force-logout                   Log out the current user without confirmation
+  sleep                          Put the machine to sleep
+  hibernate                      Hibernate the machine
+  hybrid-sleep                   Suspend the machine to both RAM and disk
+  suspend-then-hibernate         Put the machine to sleep, then hibernate it after a while
+  halt                           Halt the machine without cutting the power
+  lock-screen                    Lock the screen of the current session
+  kexec-reboot                   Reboot into the kernel loaded with kexec
+  soft-reboot                    Restart the userspace only, keeping the running kernel
+  reboot-to-firmware-setup       Reboot into the firmware setup (Linux only)
+  reboot-to-boot-entry <ID>      Reboot into the boot loader entry ID, once (Linux only)
+  reboot-to-boot-menu <SECONDS>  Reboot showing the boot loader menu for SECONDS, once (Linux only)
+  cancel                         Cancel the scheduled shut down or reboot (Linux only)
+
+Options:
+  --delay <SECONDS>   Wait before performing the action; on Linux, shutdown and reboot are
+                      scheduled instead, and can be cancelled
+  --message <TEXT>    Warn the logged in users (shutdown and reboot only); the system
+                      performs the action once the delay has elapsed
+  --dry-run           Print the backends that would be tried instead of performing the action
+  --json              Print the result as JSON
+  -h, --help          Print this help";
+
+/// How a subcommand performs its action.
+#[derive(Clone, Copy)]
+enum Perform {
+    Action(fn() -> ShutdownResult),
+    /// Takes the ID of a boot loader entry.
+    #[cfg(target_os = "linux")]
+    Entry(fn(&str) -> ShutdownResult),
+    /// Takes a number of seconds.
+    #[cfg(target_os = "linux")]
+    Timeout(fn(Duration) -> ShutdownResult),
+}
+
+impl Perform {
+    /// The placeholder of the argument the subcommand takes, if any.
+    fn argument(self) -> Option<&'static str> {
+        match self {
+            Perform::Action(_) => None,
+            #[cfg(target_os = "linux")]
+            Perform::Entry(_) => Some("an ID"),
+            #[cfg(target_os = "linux")]
+            Perform::Timeout(_) => Some("a number of seconds"),
+        }
+    }
+}
+
+/// A subcommand: its name, the action it stands for and the function performing it.
+struct Command {
+    name: &'static str,
+    /// The action listed by `plan`, `None` if the subcommand does not go through the backends.
+    action: Option<Action>,
+    /// Whether the action skips the confirmations, and so the backends listed by `plan`.
+    force: bool,
+    perform: Perform,
+}
+
+const COMMANDS: &[Command] = &[
+    Command::new("shutdown", Action::Shutdown, system_shutdown::shutdown),
+    Command::force(
+        "force-shutdown",
+        Action::Shutdown,
+        system_shutdown::force_shutdown,
+    ),
+    Command::new("reboot", Action::Reboot, system_shutdown::reboot),
+    Command::force(
+        "force-reboot",
+        Action::Reboot,
+        system_shutdown::force_reboot,
+    ),
+    Command::new("logout", Action::Logout, system_shutdown::logout),
+    Command::force(
+        "force-logout",
+        Action::Logout,
+        system_shutdown::force_logout,
+    ),
+    Command::new("sleep", Action::Sleep, system_shutdown::sleep),
+    Command::new("hibernate", Action::Hibernate, system_shutdown::hibernate),
+    Command::new(
+        "hybrid-sleep",
+        Action::HybridSleep,
+        system_shutdown::hybrid_sleep,
+    ),
+    Command::new(
+        "suspend-then-hibernate",
+        Action::SuspendThenHibernate,
+        system_shutdown::suspend_then_hibernate,
+    ),
+    Command::new("halt", Action::Halt, system_shutdown::halt),
+    Command::new(
+        "lock-screen",
+        Action::LockScreen,
+        system_shutdown::lock_screen,
+    ),
+    Command::new(
+        "kexec-reboot",
+        Action::KexecReboot,
+        system_shutdown::kexec_reboot,
+    ),
+    Command::new(
+        "soft-reboot",
+        Action::SoftReboot,
+        system_shutdown::soft_reboot,
+    ),
+];
+
+/// The subcommands available on Linux only.
+#[cfg(target_os = "linux")]
+const LINUX_COMMANDS: &[Command] = &[
+    Command::other(
+        "reboot-to-firmware-setup",
+        Perform::Action(system_shutdown::reboot_to_firmware_setup),
+    ),
+    Command::other(
+        "reboot-to-boot-entry",
+        Perform::Entry(system_shutdown::reboot_to_boot_entry),
+    ),
+    Command::other(
+        "reboot-to-boot-menu",
+        Perform::Timeout(system_shutdown::reboot_to_boot_loader_menu),
+    ),
+    Command::other("cancel", Perform::Action(system_shutdown::cancel_scheduled)),
+];
+
+#[cfg(not(target_os = "linux"))]
+const LINUX_COMMANDS: &[Command] = &[];
+
+impl Command {
+    const fn new(name: &'static str, action: Action, perform: fn() -> ShutdownResult) -> Self {
+        Command {
+            name,
+            action: Some(action),
+            force: false,
+            perform: Perform::Action(perform),
+        }
+    }
+
+    const fn force(name: &'static str, action: Action, perform: fn() -> ShutdownResult) -> Self {
+        Command {
+            name,
+            action: Some(action),
+            force: true,
+            perform: Perform::Action(perform),
+        }
+    }
+
+    /// A subcommand not going through the backends listed by `plan`.
+    #[cfg(target_os = "linux")]
+    const fn other(name: &'static str, perform: Perform) -> Self {
+        Command {
+            name,
+            action: None,
+            force: false,
+            perform,
+        }
+    }
+
+    fn find(name: &str) -> Option<&'static Command> {
+        COMMANDS
+            .iter()
+            .chain(LINUX_COMMANDS)
+            .find(|command| command.name == name)
+    }
+
+    /// The steps the subcommand would take, as printed by `--dry-run`: those of the
+    /// `*_with_message` functions or of the scheduled action, of the force variants, or of the
+    /// backends listed by `plan`.
+    #[cfg(target_os = "linux")]
+    fn plan(&self, options: &Options) -> Vec<Step> {
+        let Some(action) = self.action else {
+            return Vec::new();
+        };
+        if options.message.is_some() || self.schedule(options).is_some() {
+            let time = std::time::SystemTime::now() + Duration::from_secs(options.delay.into());
+            return system_shutdown::plan_scheduled(action, time, options.message.as_deref());
+        }
+        if self.force {
+            return system_shutdown::plan_force(action);
+        }
+        system_shutdown::plan(action)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn plan(&self, options: &Options) -> Vec<Step> {
+        match self.action {
+            Some(action) if !self.force && options.message.is_none() => {
+                system_shutdown::plan(action)
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// The function scheduling the action once the delay has elapsed, if there is one, so that
+    /// it runs without waiting for the delay.
+    #[cfg(target_os = "linux")]
+    fn schedule(&self, options: &Options) -> Option<fn(Duration) -> ShutdownResult> {
+        if options.delay == 0 || self.force {
+            return None;
+        }
+        match self.action {
+            Some(Action::Shutdown) => Some(system_shutdown::shutdown_in),
+            Some(Action::Reboot) => Some(system_shutdown::reboot_in),
+            _ => None,
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn schedule(&self, _options: &Options) -> Option<fn(Duration) -> ShutdownResult> {
+        None
+    }
+
+    /// Performs the action, through the `*_with_message` functions if there is a message.
+    fn run(&self, options: &Options) -> ShutdownResult {
+        let delay = Duration::from_secs(options.delay.into());
+        if let Some(message) = &options.message {
+            let timeout = options.delay;
+            return match self.action {
+                Some(Action::Shutdown) => {
+                    system_shutdown::shutdown_with_message(message, timeout, self.force)
+                }
+                Some(Action::Reboot) => {
+                    system_shutdown::reboot_with_message(message, timeout, self.force)
+                }
+                _ => unreachable!("checked by parse_args"),
+            };
+        }
+        if let Some(schedule) = self.schedule(options) {
+            return schedule(delay);
+        }
+        thread::sleep(delay);
+        match self.perform {
+            Perform::Action(perform) => perform(),
+            #[cfg(target_os = "linux")]
+            Perform::Entry(perform) => perform(options.argument()),
+            #[cfg(target_os = "linux")]
+            Perform::Timeout(perform) => {
+                let seconds = parse_seconds(options.argument()).expect("checked by parse_args");
+                perform(Duration::from_secs(seconds.into()))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Options {
+    delay: u32,
+    message: Option<String>,
+    dry_run: bool,
+    json: bool,
+    /// The argument of the subcommand, checked by `parse_args`.
+    argument: Option<String>,
+}
+
+impl Options {
+    #[cfg(target_os = "linux")]
+    fn argument(&self) -> &str {
+        self.argument.as_deref().expect("checked by parse_args")
+    }
+}
+
+fn parse_seconds(value: &str) -> Result<u32, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid number of seconds `{value}`"))
+}
+
+/// Parses the command line, returning `None` if the help is requested.
+fn parse_args(
+    args: impl IntoIterator<Item = String>,
+) -> Result<Option<(&'static Command, Options)>, String> {
+    let mut options = Options::default();
+    let mut command: Option<&Command> = None;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--delay" => {
+                let value = args.next().ok_or("--delay needs a number of seconds")?;
+                options.delay = parse_seconds(&value)?;
+            }
+            "--message" => {
+                options.message = Some(args.next().ok_or("--message needs a text")?);
+            }
+            "--dry-run" => options.dry_run = true,
+            "--json" => options.json = true,
+            "-h" | "--help" => return Ok(None),
+            _ if arg.starts_with('-') => return Err(format!("unknown option `{arg}`")),
+            _ => match command {
+                None => {
+                    command = Some(
+                        Command::find(&arg).ok_or_else(|| format!("unknown command `{arg}`"))?,
+                    );
+                }
+                Some(Command { perform, .. })
+                    if perform.argument().is_some() && options.argument.is_none() =>
+                {
+                    options.argument = Some(arg);
+                }
+                Some(_) => return Err(format!("unexpected argument `{arg}`")),
+            },
+        }
+    }
+    let command = command.ok_or("missing command")?;
+    if let (Some(argument), None) = (command.perform.argument(), &options.argument) {
+        return Err(format!("`{}` needs {argument}", command.name));
+    }
+    #[cfg(target_os = "linux")]
+    if let (Perform::Timeout(_), Some(value)) = (command.perform, &options.argument) {
+        parse_seconds(value)?;
+    }
+    // The messages are scheduled with logind on Linux, which cannot force the action
+    let forced = cfg!(target_os = "linux") && command.force;
+    if options.message.is_some()
+        && (forced || !matches!(command.action, Some(Action::Shutdown | Action::Reboot)))
+    {
+        return Err(format!("--message does not apply to `{}`", command.name));
+    }
+    Ok(Some((command, options)))
+}
+
+/// Quotes `text` as a JSON string.
+fn json_string(text: &str) -> String {
+    let mut json = String::with_capacity(text.len() + 2);
+    json.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+fn json_list<T>(items: &[T], to_json: impl Fn(&T) -> String) -> String {
+    let items = items.iter().map(to_json).collect::<Vec<_>>();
+    format!("[{}]", items.join(","))
+}
+
+fn status_name(status: Status) -> &'static str {
+    match status {
+        Status::Interactive => "interactive",
+        _ => "completed",
+    }
+}
+
+fn outcome_json(command: &Command, outcome: &Outcome) -> String {
+    format!(
+        "{{\"command\":{},\"ok\":true,\"backend\":{},\"invocation\":{},\"status\":\"{}\"}}",
+        json_string(command.name),
+        json_string(&outcome.backend),
+        json_string(&outcome.invocation.to_string()),
+        status_name(outcome.status),
+    )
+}
+
+fn error_json(command: &Command, error: &ShutdownError) -> String {
+    let attempts = json_list(error.attempts(), |attempt| {
+        format!(
+            "{{\"backend\":{},\"reason\":{},\"kind\":\"{:?}\"}}",
+            json_string(&attempt.backend),
+            json_string(&attempt.reason),
+            attempt.kind,
+        )
+    });
+    format!(
+        "{{\"command\":{},\"ok\":false,\"error\":{},\"kind\":\"{:?}\",\"attempts\":{attempts}}}",
+        json_string(command.name),
+        json_string(&error.to_string()),
+        error.kind(),
+    )
+}
+
+fn plan_json(command: &Command, options: &Options, steps: &[Step]) -> String {
+    let steps = json_list(steps, |step| {
+        let invocations = json_list(&step.invocations, |invocation| {
+            json_string(&invocation.to_string())
+        });
+        format!(
+            "{{\"backend\":{},\"available\":{},\"invocations\":{invocations}}}",
+            json_string(&step.backend),
+            step.available,
+        )
+    });
+    let message = options
+        .message
+        .as_deref()
+        .map_or("null".to_string(), json_string);
+    format!(
+        "{{\"command\":{},\"dry_run\":true,\"delay\":{},\"message\":{message},\"steps\":{steps}}}",
+        json_string(command.name),
+        options.delay,
+    )
+}
+
+fn print_plan(command: &Command, options: &Options, steps: &[Step]) {
+    println!(
+        "Would run `{}` after {} seconds",
+        command.name, options.delay
+    );
+    if let Some(message) = &options.message {
+        println!("Message: {message}");
+    }
+    if steps.is_empty() {
+        println!("The steps of `{}` cannot be listed", command.name);
+    }
+    for step in steps {
+        let available = if step.available {
+            "available"
+        } else {
+            "unavailable"
+        };
+        println!("- {} ({available})", step.backend);
+        for invocation in &step.invocations {
+            println!("    {invocation}");
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let (command, options) = match parse_args(env::args().skip(1)) {
+        Ok(Some(parsed)) => parsed,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(error) => {
+            eprintln!("system-shutdown: {error}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    if options.dry_run {
+        let steps = command.plan(&options);
+        if options.json {
+            println!("{}", plan_json(command, &options, &steps));
+        } else {
+            print_plan(command, &options, &steps);
+        }
+        return ExitCode::SUCCESS;
+    }
+    match command.run(&options) {
+        Ok(outcome) => {
+            if options.json {
+                println!("{}", outcome_json(command, &outcome));
+            } else {
+                println!("{}: {outcome}", command.name);
+            }
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            if options.json {
+                println!("{}", error_json(command, &error));
+            } else {
+                eprintln!("system-shutdown: {} failed: {error}", command.name);
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::ErrorKind;
+
+    use system_shutdown::{Attempt, Invocation};
+
+    fn parse(args: &[&str]) -> Result<Option<(&'static str, Options)>, String> {
+        let args = args.iter().map(|arg| arg.to_string());
+        parse_args(args).map(|parsed| parsed.map(|(command, options)| (command.name, options)))
+    }
+
+    #[test]
+    fn parse_args_reads_the_command_and_the_options() {
+        let parsed = parse(&["--delay", "60", "reboot", "--message", "bye", "--json"]);
+        let options = Options {
+            delay: 60,
+            message: Some("bye".to_string()),
+            json: true,
+            ..Options::default()
+        };
+        assert_eq!(parsed, Ok(Some(("reboot", options))));
+        let options = Options {
+            dry_run: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            parse(&["force-logout", "--dry-run"]),
+            Ok(Some(("force-logout", options)))
+        );
+        assert_eq!(parse(&["sleep", "--help"]), Ok(None));
+    }
+
+    #[test]
+    fn parse_args_rejects_invalid_command_lines() {
+        let cases: &[(&[&str], &str)] = &[
+            (&[], "missing command"),
+            (&["--dry-run"], "missing command"),
+            (&["restart"], "unknown command `restart`"),
+            (&["--force", "shutdown"], "unknown option `--force`"),
+            (&["shutdown", "now"], "unexpected argument `now`"),
+            (
+                &["shutdown", "--delay"],
+                "--delay needs a number of seconds",
+            ),
+            (
+                &["shutdown", "--delay", "-1"],
+                "invalid number of seconds `-1`",
+            ),
+            (&["reboot", "--message"], "--message needs a text"),
+            (
+                &["sleep", "--message", "bye"],
+                "--message does not apply to `sleep`",
+            ),
+        ];
+        for (args, error) in cases {
+            assert_eq!(parse(args), Err(error.to_string()), "{args:?}");
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn parse_args_reads_the_argument_of_the_command() {
+        let options = Options {
+            argument: Some("arch.conf".to_string()),
+            ..Options::default()
+        };
+        assert_eq!(
+            parse(&["reboot-to-boot-entry", "arch.conf"]),
+            Ok(Some(("reboot-to-boot-entry", options)))
+        );
+        let cases: &[(&[&str], &str)] = &[
+            (
+                &["reboot-to-boot-entry"],
+                "`reboot-to-boot-entry` needs an ID",
+            ),
+            (
+                &["reboot-to-boot-menu"],
+                "`reboot-to-boot-menu` needs a number of seconds",
+            ),
+            (
+                &["reboot-to-boot-menu", "soon"],
+                "invalid number of seconds `soon`",
+            ),
+            (
+                &["reboot-to-boot-entry", "a", "b"],
+                "unexpected argument `b`",
+            ),
+            (&["cancel", "now"], "unexpected argument `now`"),
+            (
+                &["force-shutdown", "--message", "bye"],
+                "--message does not apply to `force-shutdown`",
+            ),
+            (
+                &["reboot-to-firmware-setup", "--message", "bye"],
+                "--message does not apply to `reboot-to-firmware-setup`",
+            ),
+        ];
+        for (args, error) in cases {
+            assert_eq!(parse(args), Err(error.to_string()), "{args:?}");
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn delayed_shutdowns_and_reboots_are_scheduled() {
+        let delayed = Options {
+            delay: 60,
+            ..Options::default()
+        };
+        let scheduled = |name: &str, options: &Options| {
+            let command = Command::find(name).unwrap();
+            command.schedule(options).is_some()
+        };
+        assert!(scheduled("shutdown", &delayed));
+        assert!(scheduled("reboot", &delayed));
+        assert!(!scheduled("reboot", &Options::default()));
+        assert!(!scheduled("force-reboot", &delayed));
+        assert!(!scheduled("sleep", &delayed));
+        assert!(!scheduled("cancel", &delayed));
+    }
+
+    #[test]
+    fn json_string_escapes_the_special_characters() {
+        let cases = [
+            ("", r#""""#),
+            ("plain text", r#""plain text""#),
+            (r#"a "quoted" \\path"#, r#""a \"quoted\" \\\\path""#),
+            ("line\r\nnext\ttab", r#""line\r\nnext\ttab""#),
+            ("bell\u{7}\u{1f}", r#""bell\u0007\u001f""#),
+            ("ünïcödé ✓", r#""ünïcödé ✓""#),
+        ];
+        for (text, json) in cases {
+            assert_eq!(json_string(text), json, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn json_list_joins_the_items() {
+        assert_eq!(json_list::<&str>(&[], |item| json_string(item)), "[]");
+        assert_eq!(
+            json_list(&["a", "b\"c"], |item| json_string(item)),
+            r#"["a","b\"c"]"#
+        );
+    }
+
+    #[test]
+    fn results_are_printed_as_json() {
+        let command = Command::find("reboot").unwrap();
+        let invocation = Invocation::Command("systemctl reboot".to_string());
+        let outcome = Outcome::new("systemctl", invocation, Status::Completed);
+        assert_eq!(
+            outcome_json(command, &outcome),
+            r#"{"command":"reboot","ok":true,"backend":"systemctl","invocation":"systemctl reboot","status":"completed"}"#
+        );
+        let attempt = Attempt::new(
+            "org.freedesktop.login1",
+            "denied",
+            ErrorKind::PermissionDenied,
+        );
+        let error = ShutdownError::from_attempts(vec![attempt]);
+        let json = error_json(command, &error);
+        let expected_start = r#"{"command":"reboot","ok":false,"error":"#;
+        let expected_end = r#","kind":"PermissionDenied","attempts":[{"backend":"org.freedesktop.login1","reason":"denied","kind":"PermissionDenied"}]}"#;
+        assert!(json.starts_with(expected_start), "{json}");
+        assert!(json.ends_with(expected_end), "{json}");
+    }
+
+    #[test]
+    fn plans_are_printed_as_json() {
+        let command = Command::find("shutdown").unwrap();
+        let options = Options {
+            delay: 5,
+            ..Options::default()
+        };
+        assert_eq!(
+            plan_json(command, &options, &[]),
+            r#"{"command":"shutdown","dry_run":true,"delay":5,"message":null,"steps":[]}"#
+        );
+    }
+}
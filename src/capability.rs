@@ -0,0 +1,51 @@
+/// Whether the system allows the caller to perform an action.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Capability {
+    /// The action is allowed.
+    Yes,
+    /// The action is allowed once the user authenticates.
+    Challenge,
+    /// The action is not allowed for the caller.
+    No,
+    /// The action is not supported by the hardware or the system.
+    NotApplicable,
+    /// The system does not report whether the action is possible.
+    Unknown,
+}
+
+impl Capability {
+    /// Returns `true` if the action can be performed, possibly after authenticating.
+    pub fn is_possible(self) -> bool {
+        matches!(self, Capability::Yes | Capability::Challenge)
+    }
+}
+
+impl From<bool> for Capability {
+    fn from(allowed: bool) -> Self {
+        if allowed {
+            Capability::Yes
+        } else {
+            Capability::No
+        }
+    }
+}
+
+/// The power actions the system allows the caller to perform.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Capabilities {
+    /// Whether the machine can be shut down.
+    pub shutdown: Capability,
+    /// Whether the machine can be rebooted.
+    pub reboot: Capability,
+    /// Whether the machine can be put to sleep.
+    pub suspend: Capability,
+    /// Whether the machine can be hibernated.
+    pub hibernate: Capability,
+    /// Whether the machine can be suspended to both RAM and disk.
+    pub hybrid_sleep: Capability,
+    /// Whether the machine can be suspended, then hibernated after a while.
+    pub suspend_then_hibernate: Capability,
+    /// Whether the machine can be rebooted into a kernel loaded with kexec.
+    pub kexec_reboot: Capability,
+}
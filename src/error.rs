@@ -0,0 +1,192 @@
+use std::error::Error;
+use std::fmt;
+use std::io::{self, ErrorKind};
+
+/// A backend that was tried while performing an operation, and why it was rejected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Attempt {
+    /// The backend that was tried, e.g. `org.freedesktop.login1.Manager.PowerOff` or `shutdown -h now`.
+    pub backend: String,
+    /// The D-BUS error name or OS error message returned by the backend.
+    pub reason: String,
+    /// The category of the failure.
+    pub kind: ErrorKind,
+}
+
+impl Attempt {
+    /// Creates a new attempt record.
+    pub fn new(backend: impl Into<String>, reason: impl Into<String>, kind: ErrorKind) -> Self {
+        Attempt {
+            backend: backend.into(),
+            reason: reason.into(),
+            kind,
+        }
+    }
+}
+
+impl fmt::Display for Attempt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.backend, self.reason)
+    }
+}
+
+/// The error type for shut down, reboot and log out operations.
+///
+/// Every variant but [`ShutdownError::Io`] carries the ordered list of backends that were tried.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ShutdownError {
+    /// The caller is not allowed to perform the operation.
+    PermissionDenied(Vec<Attempt>),
+    /// The operation is not supported on this system.
+    NotSupported(Vec<Attempt>),
+    /// The operation is blocked by an inhibitor lock.
+    Inhibited(Vec<Attempt>),
+    /// The operation was cancelled by the user.
+    Cancelled(Vec<Attempt>),
+    /// None of the known backends is available on this system.
+    NoBackendAvailable(Vec<Attempt>),
+    /// A backend accepted the request but failed to carry it out.
+    CommandFailed(Vec<Attempt>),
+    /// An OS error not tied to a specific backend.
+    Io(io::Error),
+}
+
+impl ShutdownError {
+    /// Builds an error from the backends that were tried, picking the most relevant failure.
+    pub fn from_attempts(attempts: Vec<Attempt>) -> Self {
+        let has = |kind| attempts.iter().any(|attempt| attempt.kind == kind);
+        if has(ErrorKind::Interrupted) {
+            ShutdownError::Cancelled(attempts)
+        } else if has(ErrorKind::ResourceBusy) {
+            ShutdownError::Inhibited(attempts)
+        } else if has(ErrorKind::PermissionDenied) {
+            ShutdownError::PermissionDenied(attempts)
+        } else if has(ErrorKind::Other) {
+            ShutdownError::CommandFailed(attempts)
+        } else if has(ErrorKind::Unsupported) {
+            ShutdownError::NotSupported(attempts)
+        } else {
+            ShutdownError::NoBackendAvailable(attempts)
+        }
+    }
+
+    /// Returns the backends that were tried, in order.
+    pub fn attempts(&self) -> &[Attempt] {
+        match self {
+            ShutdownError::PermissionDenied(attempts)
+            | ShutdownError::NotSupported(attempts)
+            | ShutdownError::Inhibited(attempts)
+            | ShutdownError::Cancelled(attempts)
+            | ShutdownError::NoBackendAvailable(attempts)
+            | ShutdownError::CommandFailed(attempts) => attempts,
+            ShutdownError::Io(_) => &[],
+        }
+    }
+
+    /// Returns the corresponding [`std::io::ErrorKind`] for this error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            ShutdownError::PermissionDenied(_) => ErrorKind::PermissionDenied,
+            ShutdownError::NotSupported(_) => ErrorKind::Unsupported,
+            ShutdownError::Inhibited(_) => ErrorKind::ResourceBusy,
+            ShutdownError::Cancelled(_) => ErrorKind::Interrupted,
+            ShutdownError::NoBackendAvailable(_) => ErrorKind::NotFound,
+            ShutdownError::CommandFailed(_) => ErrorKind::Other,
+            ShutdownError::Io(error) => error.kind(),
+        }
+    }
+}
+
+impl fmt::Display for ShutdownError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            ShutdownError::PermissionDenied(_) => "permission denied",
+            ShutdownError::NotSupported(_) => "operation not supported",
+            ShutdownError::Inhibited(_) => "operation inhibited",
+            ShutdownError::Cancelled(_) => "operation cancelled",
+            ShutdownError::NoBackendAvailable(_) => "no backend available",
+            ShutdownError::CommandFailed(_) => "command failed",
+            ShutdownError::Io(error) => return error.fmt(f),
+        };
+        f.write_str(description)?;
+        for (i, attempt) in self.attempts().iter().enumerate() {
+            f.write_str(if i == 0 { " (" } else { "; " })?;
+            attempt.fmt(f)?;
+        }
+        if !self.attempts().is_empty() {
+            f.write_str(")")?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ShutdownError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ShutdownError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ShutdownError {
+    fn from(error: io::Error) -> Self {
+        ShutdownError::Io(error)
+    }
+}
+
+impl From<ShutdownError> for io::Error {
+    fn from(error: ShutdownError) -> Self {
+        match error {
+            ShutdownError::Io(error) => error,
+            error => io::Error::new(error.kind(), error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_from(kinds: &[ErrorKind]) -> ShutdownError {
+        let attempts = kinds
+            .iter()
+            .map(|&kind| Attempt::new("backend", "reason", kind))
+            .collect();
+        ShutdownError::from_attempts(attempts)
+    }
+
+    #[test]
+    fn from_attempts_picks_the_most_relevant_kind() {
+        use ErrorKind::*;
+        let cases: &[(&[ErrorKind], ErrorKind)] = &[
+            (&[], NotFound),
+            (&[NotFound], NotFound),
+            (&[NotFound, Unsupported], Unsupported),
+            (&[Unsupported, Other], Other),
+            (&[Other, PermissionDenied], PermissionDenied),
+            (&[PermissionDenied, ResourceBusy], ResourceBusy),
+            (&[ResourceBusy, Interrupted], Interrupted),
+            (
+                &[Interrupted, PermissionDenied, Other, NotFound],
+                Interrupted,
+            ),
+            (
+                &[NotFound, Unsupported, Other, PermissionDenied],
+                PermissionDenied,
+            ),
+        ];
+        for (kinds, expected) in cases {
+            assert_eq!(error_from(kinds).kind(), *expected, "attempts: {kinds:?}");
+        }
+    }
+
+    #[test]
+    fn from_attempts_keeps_the_attempts_in_order() {
+        let error = error_from(&[ErrorKind::NotFound, ErrorKind::Other]);
+        assert!(matches!(error, ShutdownError::CommandFailed(_)));
+        let kinds = error.attempts().iter().map(|a| a.kind).collect::<Vec<_>>();
+        assert_eq!(kinds, [ErrorKind::NotFound, ErrorKind::Other]);
+    }
+}
@@ -18,6 +18,11 @@
 //! ```
 //!
 //! In most of the systems it does not requires the user to be root/admin.
+//!
+//! On Linux, the `async` feature adds non-blocking versions of the power actions in the
+//! `asynchronous` module.
+//!
+//! The `cli` feature builds the `system-shutdown` command-line tool.
 
 #[cfg(target_os = "linux")]
 #[path = "linux.rs"]
@@ -32,24 +37,73 @@ pub use os::request_permission_dialog;
 #[cfg(target_os = "windows")]
 #[path = "windows.rs"]
 mod os;
-#[cfg(target_os = "windows")]
-pub use os::{reboot_with_message, shutdown_with_message};
 
-use std::io;
+mod error;
+pub use error::{Attempt, ShutdownError};
+
+mod capability;
+pub use capability::{Capabilities, Capability};
+
+mod outcome;
+pub use outcome::{Invocation, Outcome, Status, Step};
+
+#[cfg(target_os = "linux")]
+mod backend;
+#[cfg(all(target_os = "linux", feature = "async"))]
+pub use backend::BoxFuture;
+#[cfg(target_os = "linux")]
+pub use backend::{PowerBackend, Registry, registry, set_registry};
+#[cfg(all(target_os = "linux", feature = "async"))]
+pub use os::asynchronous;
+#[cfg(target_os = "linux")]
+pub use os::{
+    ActionOptions, EmergencyReboot, InhibitMode, InhibitTarget, Inhibitor, InhibitorLock,
+    LoginSession, LoginUser, PowerEvent, PowerEvents, ScheduledShutdown, cancel_scheduled,
+    list_boot_entries, list_inhibitors, list_sessions, list_users, logout_session, logout_user,
+    perform_with, plan_force, plan_scheduled, reboot_at, reboot_in, reboot_to_boot_entry,
+    reboot_to_boot_loader_menu, reboot_to_firmware_setup, scheduled, shutdown_at, shutdown_in,
+};
 
 #[doc(hidden)]
 #[macro_export]
 macro_rules! not_implemented {
     () => {
-        Err(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            "feature not implemented yet",
-        ))
+        Err($crate::ShutdownError::NotSupported(Vec::new()))
     };
 }
 
+/// A power action that can be performed on the machine.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Action {
+    /// Shut down the machine.
+    Shutdown,
+    /// Reboot the machine.
+    Reboot,
+    /// Log out the user.
+    Logout,
+    /// Put the machine to sleep.
+    Sleep,
+    /// Hibernate the machine.
+    Hibernate,
+    /// Suspend the machine to both RAM and disk.
+    HybridSleep,
+    /// Put the machine to sleep, then hibernate it after a while.
+    SuspendThenHibernate,
+    /// Reboot into the kernel loaded with kexec, skipping the firmware and the boot loader.
+    KexecReboot,
+    /// Restart the userspace only, keeping the running kernel.
+    SoftReboot,
+    /// Halt the machine, stopping the CPU without cutting the power.
+    Halt,
+    /// Lock the screen of the current session.
+    LockScreen,
+}
+
 /// A specialized `Result` type for shut down, reboot and log out operations.
-pub type ShutdownResult = io::Result<()>;
+///
+/// On success it describes which backend performed the operation.
+pub type ShutdownResult<T = Outcome> = Result<T, ShutdownError>;
 
 /// Calls the OS-specific function to shut down the machine.
 pub fn shutdown() -> ShutdownResult {
@@ -61,6 +115,17 @@ pub fn force_shutdown() -> ShutdownResult {
     os::force_shutdown()
 }
 
+/// Calls the OS-specific function to shut down the machine in `timeout` seconds, showing `message`
+/// to the logged in users. If `force_close_apps` is `true`, applications with unsaved changes are
+/// closed without asking.
+pub fn shutdown_with_message(
+    message: &str,
+    timeout: u32,
+    force_close_apps: bool,
+) -> ShutdownResult {
+    os::shutdown_with_message(message, timeout, force_close_apps)
+}
+
 /// Calls the OS-specific function to reboot the machine.
 pub fn reboot() -> ShutdownResult {
     os::reboot()
@@ -71,6 +136,13 @@ pub fn force_reboot() -> ShutdownResult {
     os::force_reboot()
 }
 
+/// Calls the OS-specific function to reboot the machine in `timeout` seconds, showing `message`
+/// to the logged in users. If `force_close_apps` is `true`, applications with unsaved changes are
+/// closed without asking.
+pub fn reboot_with_message(message: &str, timeout: u32, force_close_apps: bool) -> ShutdownResult {
+    os::reboot_with_message(message, timeout, force_close_apps)
+}
+
 /// Calls the OS-specific function to log out the user.
 pub fn logout() -> ShutdownResult {
     os::logout()
@@ -90,3 +162,63 @@ pub fn sleep() -> ShutdownResult {
 pub fn hibernate() -> ShutdownResult {
     os::hibernate()
 }
+
+/// Calls the OS-specific function to suspend the machine to both RAM and disk.
+pub fn hybrid_sleep() -> ShutdownResult {
+    os::hybrid_sleep()
+}
+
+/// Calls the OS-specific function to put the machine to sleep, then hibernate it after a while.
+pub fn suspend_then_hibernate() -> ShutdownResult {
+    os::suspend_then_hibernate()
+}
+
+/// Calls the OS-specific function to lock the screen of the current session.
+pub fn lock_screen() -> ShutdownResult {
+    os::lock_screen()
+}
+
+/// Calls the OS-specific function to halt the machine, without cutting the power.
+pub fn halt() -> ShutdownResult {
+    os::halt()
+}
+
+/// Calls the OS-specific function to reboot the machine into the kernel loaded with kexec.
+pub fn kexec_reboot() -> ShutdownResult {
+    os::kexec_reboot()
+}
+
+/// Calls the OS-specific function to restart the userspace only, keeping the running kernel.
+pub fn soft_reboot() -> ShutdownResult {
+    os::soft_reboot()
+}
+
+/// Lists the steps that would be tried to perform `action`, in order, without invoking any of them.
+pub fn plan(action: Action) -> Vec<Step> {
+    os::plan(action)
+}
+
+/// Calls the OS-specific function to query which power actions are allowed.
+pub fn capabilities() -> Capabilities {
+    os::capabilities()
+}
+
+/// Checks whether the machine can be shut down.
+pub fn can_shutdown() -> Capability {
+    os::can_shutdown()
+}
+
+/// Checks whether the machine can be rebooted.
+pub fn can_reboot() -> Capability {
+    os::can_reboot()
+}
+
+/// Checks whether the machine can be put to sleep.
+pub fn can_suspend() -> Capability {
+    os::can_suspend()
+}
+
+/// Checks whether the machine can be hibernated.
+pub fn can_hibernate() -> Capability {
+    os::can_hibernate()
+}
@@ -1,71 +1,233 @@
-use std::fs::File;
-use std::io::{Error, ErrorKind, Write};
-use std::process::Command;
+use std::io::{self, ErrorKind};
+use std::mem;
+use std::process::{Command, Output};
+use std::sync::Arc;
 
-use super::not_implemented;
+#[cfg(feature = "async")]
+use super::BoxFuture;
 use super::ShutdownResult;
+use super::backend::is_cancelled;
+use super::{
+    Action, Attempt, Invocation, Outcome, PowerBackend, ShutdownError, Status, Step, registry,
+};
+use super::{Capabilities, Capability};
 
-use zbus::export::serde::Serialize;
-use zbus::zvariant::DynamicType;
-
-fn name_has_owner(name: &str) -> bool {
-    if let Ok(conn) = zbus::blocking::Connection::session() {
-        let reply = conn.call_method(
-            Some("org.freedesktop.DBus"),
-            "/",
-            Some("org.freedesktop.DBus"),
-            "NameHasOwner",
-            &(name),
-        );
-        return reply.and_then(|r| r.body().deserialize()).unwrap_or(false);
+use zbus::Message;
+use zbus::blocking::{Connection, Proxy};
+use zbus::export::serde::de::DeserializeOwned;
+use zbus::export::serde::{Serialize, Serializer};
+use zbus::zvariant::{DynamicType, OwnedValue, Signature, Type};
+
+#[cfg(feature = "async")]
+#[path = "linux/asynchronous.rs"]
+pub mod asynchronous;
+
+#[path = "linux/boot.rs"]
+mod boot;
+pub use boot::{
+    list_boot_entries, reboot_to_boot_entry, reboot_to_boot_loader_menu, reboot_to_firmware_setup,
+};
+
+#[path = "linux/inhibit.rs"]
+mod inhibit;
+pub use inhibit::{InhibitMode, InhibitTarget, Inhibitor, InhibitorLock, list_inhibitors};
+
+#[path = "linux/listen.rs"]
+mod listen;
+pub use listen::{PowerEvent, PowerEvents};
+
+#[path = "linux/schedule.rs"]
+mod schedule;
+pub use schedule::{
+    ScheduledShutdown, cancel_scheduled, plan_scheduled, reboot_at, reboot_in, reboot_with_message,
+    scheduled, shutdown_at, shutdown_in, shutdown_with_message,
+};
+
+#[path = "linux/session.rs"]
+mod session;
+pub use session::{
+    LoginSession, LoginUser, list_sessions, list_users, logout_session, logout_user,
+};
+
+#[path = "linux/sysrq.rs"]
+mod sysrq;
+pub use sysrq::EmergencyReboot;
+use sysrq::SysRq;
+
+/// The message bus a D-BUS service is reachable on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Bus {
+    /// The per-user session bus, used by desktop session managers.
+    Session,
+    /// The system-wide bus, used by logind, systemd, UPower, ConsoleKit and HAL.
+    System,
+}
+
+impl Bus {
+    fn connect(self) -> zbus::Result<Connection> {
+        match self {
+            Bus::Session => Connection::session(),
+            Bus::System => Connection::system(),
+        }
+    }
+}
+
+fn name_has_owner(conn: &Connection, name: &str) -> bool {
+    let reply = conn.call_method(
+        Some("org.freedesktop.DBus"),
+        "/",
+        Some("org.freedesktop.DBus"),
+        "NameHasOwner",
+        &(name),
+    );
+    reply.and_then(|r| r.body().deserialize()).unwrap_or(false)
+}
+
+/// Maps a D-BUS error name to the category of failure it represents.
+fn dbus_error_kind(name: &str) -> ErrorKind {
+    match name {
+        "org.freedesktop.DBus.Error.AccessDenied"
+        | "org.freedesktop.DBus.Error.AuthFailed"
+        | "org.freedesktop.DBus.Error.InteractiveAuthorizationRequired"
+        | "org.freedesktop.PolicyKit1.Error.NotAuthorized" => ErrorKind::PermissionDenied,
+        "org.freedesktop.DBus.Error.NotSupported"
+        | "org.freedesktop.DBus.Error.UnknownMethod"
+        | "org.freedesktop.DBus.Error.UnknownObject"
+        | "org.freedesktop.DBus.Error.UnknownInterface"
+        | "org.freedesktop.login1.SleepVerbNotSupported" => ErrorKind::Unsupported,
+        "org.freedesktop.DBus.Error.ServiceUnknown"
+        | "org.freedesktop.DBus.Error.NameHasNoOwner" => ErrorKind::NotFound,
+        "org.freedesktop.PolicyKit1.Error.Cancelled" => ErrorKind::Interrupted,
+        // Code 19 is G_IO_ERROR_CANCELLED, returned when the user dismisses a GLib based dialog
+        name if name.contains("org.gtk.GDBus.UnmappedGError.Quark")
+            && name.ends_with(".Code19") =>
+        {
+            ErrorKind::Interrupted
+        }
+        name if name.contains("Inhibit") => ErrorKind::ResourceBusy,
+        _ => ErrorKind::Other,
     }
-    false
 }
 
 fn dbus_send<B: Serialize + DynamicType>(
+    attempts: &mut Vec<Attempt>,
+    bus: Bus,
     destination: &str,
     path: &str,
     interface: &str,
     method: &str,
     body: &B,
 ) -> bool {
-    if name_has_owner(destination) {
-        if let Ok(conn) = zbus::blocking::Connection::session() {
-            let reply = conn.call_method(Some(destination), path, Some(interface), method, body);
-            if let Err(e) = &reply {
-                if let zbus::Error::MethodError(name, _, _) = e {
-                    let error_str = name.as_str();
-                    if error_str.contains("org.gtk.GDBus.UnmappedGError.Quark")
-                        && error_str.contains(".Code19")
-                    {
-                        // Code 19 is G_IO_ERROR_CANCELLED
-                        return true;
-                    }
-                }
-            }
-            return reply.is_ok();
+    dbus_call(attempts, bus, destination, path, interface, method, body).is_some()
+}
+
+/// Calls a D-BUS method and returns its reply, recording why it failed.
+fn dbus_call<B: Serialize + DynamicType>(
+    attempts: &mut Vec<Attempt>,
+    bus: Bus,
+    destination: &str,
+    path: &str,
+    interface: &str,
+    method: &str,
+    body: &B,
+) -> Option<Message> {
+    let backend = format!("{interface}.{method}");
+    let conn = match bus.connect() {
+        Ok(conn) => conn,
+        Err(error) => {
+            attempts.push(Attempt::new(
+                backend,
+                error.to_string(),
+                ErrorKind::NotFound,
+            ));
+            return None;
+        }
+    };
+    if !name_has_owner(&conn, destination) {
+        attempts.push(Attempt::new(
+            backend,
+            "org.freedesktop.DBus.Error.NameHasNoOwner",
+            ErrorKind::NotFound,
+        ));
+        return None;
+    }
+    match conn.call_method(Some(destination), path, Some(interface), method, body) {
+        Ok(reply) => Some(reply),
+        Err(error) => {
+            check_reply::<()>(attempts, backend, Err(error));
+            None
         }
     }
-    false
+}
+
+/// Records why a D-BUS call failed, returning whether it succeeded.
+fn check_reply<T>(attempts: &mut Vec<Attempt>, backend: String, reply: zbus::Result<T>) -> bool {
+    match reply {
+        Ok(_) => true,
+        Err(zbus::Error::MethodError(name, _, _)) => {
+            let error_str = name.as_str();
+            attempts.push(Attempt::new(backend, error_str, dbus_error_kind(error_str)));
+            false
+        }
+        Err(error) => {
+            attempts.push(Attempt::new(backend, error.to_string(), ErrorKind::Other));
+            false
+        }
+    }
+}
+
+/// Calls a D-BUS method and returns its reply, or `None` if the service is not running or fails.
+fn dbus_query<B: Serialize + DynamicType, R: DeserializeOwned + Type>(
+    bus: Bus,
+    destination: &str,
+    path: &str,
+    interface: &str,
+    method: &str,
+    body: &B,
+) -> Option<R> {
+    let conn = bus.connect().ok()?;
+    if !name_has_owner(&conn, destination) {
+        return None;
+    }
+    let reply = conn
+        .call_method(Some(destination), path, Some(interface), method, body)
+        .ok()?;
+    reply.body().deserialize().ok()
 }
 
 fn run_command(command: &str, args: &[&str]) -> ShutdownResult {
-    let mut cmd = Command::new(command);
-    cmd.args(args);
-    match cmd.output() {
+    command_result(command, args, Command::new(command).args(args).output())
+}
+
+/// Turns the output of a command into the result of the action it performs.
+fn command_result(command: &str, args: &[&str], output: io::Result<Output>) -> ShutdownResult {
+    let command_line = [command]
+        .iter()
+        .chain(args)
+        .copied()
+        .collect::<Vec<_>>()
+        .join(" ");
+    let attempt = match output {
         Ok(output) => {
             if output.status.success() && output.stderr.is_empty() {
-                return Ok(());
+                let invocation = Invocation::Command(command_line);
+                return Ok(Outcome::new("command", invocation, Status::Completed));
             }
-            Err(Error::new(
-                ErrorKind::Other,
-                String::from_utf8_lossy(&output.stderr).into_owned(),
-            ))
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let reason = match stderr.trim() {
+                "" => output.status.to_string(),
+                stderr => stderr.to_string(),
+            };
+            Attempt::new(command_line, reason, ErrorKind::Other)
         }
-        Err(error) => Err(error),
-    }
+        Err(error) => Attempt::new(command_line, error.to_string(), error.kind()),
+    };
+    Err(ShutdownError::from_attempts(vec![attempt]))
 }
 
+/// The audit session ID of processes not belonging to any session.
+const UNSET_SESSION_ID: &str = "4294967295";
+
 fn get_session_id() -> String {
     let mut session = std::env::var("XDG_SESSION_ID").unwrap_or_default();
     if session.is_empty() {
@@ -74,9 +236,1035 @@ fn get_session_id() -> String {
             .trim()
             .to_string();
     }
+    if session == UNSET_SESSION_ID {
+        session.clear();
+    }
     session
 }
 
+/// Sends `signal` to the process `pid`, ignoring processes that already exited.
+fn kill(pid: libc::pid_t, signal: libc::c_int) -> io::Result<()> {
+    // SAFETY: kill(2) has no memory safety requirements.
+    if unsafe { libc::kill(pid, signal) } == -1 {
+        let error = io::Error::last_os_error();
+        // ESRCH - the process is gone, or there was no process to signal
+        if error.raw_os_error() != Some(libc::ESRCH) {
+            return Err(error);
+        }
+    }
+    Ok(())
+}
+
+/// Lists the IDs of the processes of `session_id`, except the current one.
+///
+/// logind places the processes of a session in the `session-<id>.scope` cgroup. On systems
+/// without it, the processes are matched by their audit session ID instead.
+fn session_processes(session_id: &str) -> Vec<libc::pid_t> {
+    let own_pid = std::process::id();
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    let pids = entries
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u32>().ok())
+        .filter(|&pid| pid != own_pid)
+        .collect::<Vec<_>>();
+    let scope = format!("session-{session_id}.scope");
+    let in_scope = |pid: &u32| {
+        std::fs::read_to_string(format!("/proc/{pid}/cgroup")).is_ok_and(|cgroups| {
+            cgroups
+                .lines()
+                .filter_map(|line| line.splitn(3, ':').nth(2))
+                .any(|path| path.split('/').any(|name| name == scope))
+        })
+    };
+    let in_audit_session = |pid: &u32| {
+        std::fs::read_to_string(format!("/proc/{pid}/sessionid"))
+            .is_ok_and(|audit_session| audit_session.trim() == session_id)
+    };
+    let mut processes = pids.iter().copied().filter(in_scope).collect::<Vec<_>>();
+    if processes.is_empty() {
+        processes = pids.into_iter().filter(in_audit_session).collect();
+    }
+    processes
+        .into_iter()
+        .filter_map(|pid| libc::pid_t::try_from(pid).ok())
+        .collect()
+}
+
+/// Sends `SIGKILL` to the processes of the current session, the last resort of [`force_logout`].
+fn kill_session() -> ShutdownResult {
+    let fail = |reason: String| {
+        let attempt = Attempt::new("kill", reason, ErrorKind::NotFound);
+        ShutdownError::from_attempts(vec![attempt])
+    };
+    let session_id = get_session_id();
+    if session_id.is_empty() {
+        return Err(fail(
+            "could not determine session ID for logout".to_string(),
+        ));
+    }
+    let pids = session_processes(&session_id);
+    if pids.is_empty() {
+        return Err(fail(format!("no processes found in session {session_id}")));
+    }
+    let attempts = pids
+        .into_iter()
+        .filter_map(|pid| {
+            let error = kill(pid, libc::SIGKILL).err()?;
+            Some(Attempt::new(
+                format!("kill {pid}"),
+                error.to_string(),
+                error.kind(),
+            ))
+        })
+        .collect::<Vec<_>>();
+    if !attempts.is_empty() {
+        return Err(ShutdownError::from_attempts(attempts));
+    }
+    let invocation = Invocation::Native(format!("kill(SIGKILL) on session {session_id}"));
+    Ok(Outcome::new("kill", invocation, Status::Completed))
+}
+
+/// Arguments of a D-BUS method call made by a [`DBusBackend`].
+#[derive(Clone, Copy)]
+enum Args {
+    None,
+    Bool(bool),
+    Bools(bool, bool),
+    Int(i32),
+    Ints(i32, i32, i32),
+    Flags(u64),
+    Str(&'static str),
+    SessionId,
+    /// The current session ID, followed by who to signal and the signal number.
+    SessionSignal(&'static str, i32),
+}
+
+impl Args {
+    /// Builds the body of a call to `backend`, failing if it needs the session ID and there is
+    /// none.
+    fn body(self, backend: String) -> Result<Body, Attempt> {
+        let session_id = || match get_session_id() {
+            session_id if session_id.is_empty() => Err(Attempt::new(
+                backend,
+                "could not determine the session ID",
+                ErrorKind::NotFound,
+            )),
+            session_id => Ok(session_id),
+        };
+        Ok(match self {
+            Args::None => Body::None,
+            Args::Bool(a) => Body::Bool(a),
+            Args::Bools(a, b) => Body::Bools(a, b),
+            Args::Int(a) => Body::Int(a),
+            Args::Ints(a, b, c) => Body::Ints(a, b, c),
+            Args::Flags(a) => Body::Flags(a),
+            Args::Str(a) => Body::Str(a),
+            Args::SessionId => Body::SessionId(session_id()?),
+            Args::SessionSignal(who, signal) => Body::SessionSignal(session_id()?, who, signal),
+        })
+    }
+}
+
+/// The body of a D-BUS method call, built from [`Args`] by both the blocking and the async
+/// transports.
+enum Body {
+    None,
+    Bool(bool),
+    Bools(bool, bool),
+    Int(i32),
+    Ints(i32, i32, i32),
+    Flags(u64),
+    Str(&'static str),
+    SessionId(String),
+    SessionSignal(String, &'static str, i32),
+}
+
+impl Serialize for Body {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Body::None => ().serialize(serializer),
+            Body::Bool(a) => a.serialize(serializer),
+            Body::Bools(a, b) => (a, b).serialize(serializer),
+            Body::Int(a) => a.serialize(serializer),
+            Body::Ints(a, b, c) => (a, b, c).serialize(serializer),
+            Body::Flags(a) => a.serialize(serializer),
+            Body::Str(a) => a.serialize(serializer),
+            Body::SessionId(a) => a.serialize(serializer),
+            Body::SessionSignal(a, b, c) => (a, b, c).serialize(serializer),
+        }
+    }
+}
+
+impl DynamicType for Body {
+    fn signature(&self) -> Signature {
+        match self {
+            Body::None => <()>::SIGNATURE,
+            Body::Bool(_) => bool::SIGNATURE,
+            Body::Bools(..) => <(bool, bool)>::SIGNATURE,
+            Body::Int(_) => i32::SIGNATURE,
+            Body::Ints(..) => <(i32, i32, i32)>::SIGNATURE,
+            Body::Flags(_) => u64::SIGNATURE,
+            Body::Str(_) | Body::SessionId(_) => <&str>::SIGNATURE,
+            Body::SessionSignal(..) => <(&str, &str, i32)>::SIGNATURE,
+        }
+        .clone()
+    }
+}
+
+/// A D-BUS method performing an action.
+struct Method {
+    action: Action,
+    name: &'static str,
+    args: Args,
+    /// The state the action is left in once the method returns.
+    status: Status,
+}
+
+impl Method {
+    const fn new(action: Action, name: &'static str, args: Args) -> Self {
+        Method {
+            action,
+            name,
+            args,
+            status: Status::Completed,
+        }
+    }
+
+    /// A method handing the request to a confirmation dialog.
+    const fn interactive(action: Action, name: &'static str, args: Args) -> Self {
+        Method {
+            status: Status::Interactive,
+            ..Method::new(action, name, args)
+        }
+    }
+}
+
+/// A built-in backend calling the methods of a D-BUS service.
+struct DBusBackend {
+    bus: Bus,
+    destination: &'static str,
+    path: &'static str,
+    interface: &'static str,
+    /// The methods to call for each action, in order.
+    methods: &'static [Method],
+}
+
+impl DBusBackend {
+    fn invocation(&self, method: &str) -> Invocation {
+        Invocation::DBus {
+            destination: self.destination.to_string(),
+            method: format!("{}.{}", self.interface, method),
+        }
+    }
+
+    /// Calls `method` with `body`, recording why it failed.
+    fn send<B: Serialize + DynamicType>(
+        &self,
+        attempts: &mut Vec<Attempt>,
+        method: &str,
+        body: &B,
+    ) -> bool {
+        let (bus, dest, path, iface) = (self.bus, self.destination, self.path, self.interface);
+        dbus_send(attempts, bus, dest, path, iface, method, body)
+    }
+
+    /// Calls `method` with `body` and returns its reply, recording why it failed.
+    fn request<B, R>(&self, attempts: &mut Vec<Attempt>, method: &str, body: &B) -> Option<R>
+    where
+        B: Serialize + DynamicType,
+        R: DeserializeOwned + Type,
+    {
+        let (bus, dest, path, iface) = (self.bus, self.destination, self.path, self.interface);
+        let reply = dbus_call(attempts, bus, dest, path, iface, method, body)?;
+        self.deserialize(attempts, method, &reply)
+    }
+
+    /// Reads the reply to `method`, recording why it does not have the expected type.
+    fn deserialize<R>(
+        &self,
+        attempts: &mut Vec<Attempt>,
+        method: &str,
+        reply: &Message,
+    ) -> Option<R>
+    where
+        R: DeserializeOwned + Type,
+    {
+        match reply.body().deserialize() {
+            Ok(reply) => Some(reply),
+            Err(error) => {
+                let backend = format!("{}.{method}", self.interface);
+                attempts.push(Attempt::new(backend, error.to_string(), ErrorKind::Other));
+                None
+            }
+        }
+    }
+
+    /// Reads the property `name`, or `None` if the service is not running or does not have it.
+    fn property<R>(&self, name: &str) -> Option<R>
+    where
+        R: TryFrom<OwnedValue>,
+        R::Error: Into<zbus::Error>,
+    {
+        let conn = self.bus.connect().ok()?;
+        if !name_has_owner(&conn, self.destination) {
+            return None;
+        }
+        let proxy = Proxy::new(&conn, self.destination, self.path, self.interface).ok()?;
+        proxy.get_property(name).ok()
+    }
+
+    /// Builds the body of a call to `method`, recording why it cannot be built.
+    fn body(&self, attempts: &mut Vec<Attempt>, method: &str, args: Args) -> Option<Body> {
+        args.body(format!("{}.{method}", self.interface))
+            .map_err(|attempt| attempts.push(attempt))
+            .ok()
+    }
+
+    fn call(&self, attempts: &mut Vec<Attempt>, method: &str, args: Args) -> bool {
+        let Some(body) = self.body(attempts, method, args) else {
+            return false;
+        };
+        self.send(attempts, method, &body)
+    }
+
+    /// The methods performing `action`, in order.
+    fn methods_for(&self, action: Action) -> impl Iterator<Item = &Method> {
+        self.methods.iter().filter(move |m| m.action == action)
+    }
+
+    /// Turns the result of calling `method` into the result of the action, or `None` if the next
+    /// method is to be tried.
+    fn settle(
+        &self,
+        method: &Method,
+        sent: bool,
+        attempts: &mut Vec<Attempt>,
+    ) -> Option<ShutdownResult> {
+        if sent {
+            let invocation = self.invocation(method.name);
+            return Some(Ok(Outcome::new(
+                self.destination,
+                invocation,
+                method.status,
+            )));
+        }
+        // The user dismissed the dialog, do not ask again with another method
+        is_cancelled(attempts).then(|| Err(ShutdownError::from_attempts(mem::take(attempts))))
+    }
+}
+
+/// Returns `result`, listing the failures in `attempts` before its own if it failed.
+fn after_attempts<T>(mut attempts: Vec<Attempt>, result: ShutdownResult<T>) -> ShutdownResult<T> {
+    result.map_err(|error| {
+        attempts.extend_from_slice(error.attempts());
+        ShutdownError::from_attempts(attempts)
+    })
+}
+
+impl PowerBackend for DBusBackend {
+    fn name(&self) -> &str {
+        self.destination
+    }
+
+    fn probe(&self) -> bool {
+        self.bus
+            .connect()
+            .is_ok_and(|conn| name_has_owner(&conn, self.destination))
+    }
+
+    fn supports(&self, action: Action) -> bool {
+        self.methods_for(action).next().is_some()
+    }
+
+    fn perform(&self, action: Action) -> ShutdownResult {
+        let mut attempts = Vec::new();
+        for method in self.methods_for(action) {
+            let sent = self.call(&mut attempts, method.name, method.args);
+            if let Some(result) = self.settle(method, sent, &mut attempts) {
+                return result;
+            }
+        }
+        Err(ShutdownError::from_attempts(attempts))
+    }
+
+    fn invocations(&self, action: Action) -> Vec<Invocation> {
+        self.methods_for(action)
+            .map(|m| self.invocation(m.name))
+            .collect()
+    }
+
+    #[cfg(feature = "async")]
+    fn probe_async(&self) -> BoxFuture<'_, bool> {
+        Box::pin(self.probe_nonblocking())
+    }
+
+    #[cfg(feature = "async")]
+    fn perform_async(&self, action: Action) -> BoxFuture<'_, ShutdownResult> {
+        Box::pin(self.perform_nonblocking(action))
+    }
+}
+
+/// The built-in backend running a system command as a last resort.
+struct CommandBackend;
+
+impl PowerBackend for CommandBackend {
+    fn name(&self) -> &str {
+        "command"
+    }
+
+    fn probe(&self) -> bool {
+        true
+    }
+
+    fn supports(&self, action: Action) -> bool {
+        matches!(
+            action,
+            Action::Shutdown
+                | Action::Reboot
+                | Action::Logout
+                | Action::Sleep
+                | Action::Hibernate
+                | Action::HybridSleep
+                | Action::SuspendThenHibernate
+                | Action::KexecReboot
+                | Action::SoftReboot
+                | Action::Halt
+                | Action::LockScreen
+        )
+    }
+
+    fn perform(&self, action: Action) -> ShutdownResult {
+        let mut attempts = Vec::new();
+        for line in CommandBackend::command_lines(action)? {
+            let args = line[1..].iter().map(String::as_str).collect::<Vec<_>>();
+            match run_command(&line[0], &args) {
+                Ok(outcome) => return Ok(outcome),
+                Err(error) => attempts.extend_from_slice(error.attempts()),
+            }
+        }
+        Err(ShutdownError::from_attempts(attempts))
+    }
+
+    fn invocations(&self, action: Action) -> Vec<Invocation> {
+        CommandBackend::command_lines(action)
+            .map(|lines| {
+                lines
+                    .into_iter()
+                    .map(|line| Invocation::Command(line.join(" ")))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    #[cfg(feature = "async")]
+    fn perform_async(&self, action: Action) -> BoxFuture<'_, ShutdownResult> {
+        Box::pin(self.perform_nonblocking(action))
+    }
+}
+
+impl CommandBackend {
+    /// The command lines run for `action` until one succeeds, each starting with the program name.
+    fn command_lines(action: Action) -> ShutdownResult<Vec<Vec<String>>> {
+        let lines: &[&str] = match action {
+            Action::Shutdown => &["shutdown -h now"],
+            Action::Reboot => &["shutdown -r now"],
+            Action::Logout => {
+                let session_id = get_session_id();
+                if session_id.is_empty() {
+                    return Err(ShutdownError::NoBackendAvailable(vec![Attempt::new(
+                        "loginctl kill-session",
+                        "could not determine session ID for logout",
+                        ErrorKind::NotFound,
+                    )]));
+                }
+                return Ok(vec![vec![
+                    "loginctl".into(),
+                    "kill-session".into(),
+                    session_id,
+                ]]);
+            }
+            Action::Sleep => &["systemctl suspend"],
+            Action::Hibernate => &["systemctl hibernate"],
+            Action::HybridSleep => &["systemctl hybrid-sleep"],
+            Action::SuspendThenHibernate => &["systemctl suspend-then-hibernate"],
+            Action::KexecReboot => &["systemctl kexec"],
+            Action::SoftReboot => &["systemctl soft-reboot"],
+            // `halt` is tried where `shutdown` is missing, e.g. with BusyBox
+            Action::Halt => &["shutdown -H now", "halt"],
+            Action::LockScreen => &["xdg-screensaver lock"],
+        };
+        Ok(lines
+            .iter()
+            .map(|line| line.split(' ').map(String::from).collect())
+            .collect())
+    }
+}
+
+const GNOME: DBusBackend = DBusBackend {
+    bus: Bus::Session,
+    destination: "org.gnome.SessionManager",
+    path: "/org/gnome/SessionManager",
+    interface: "org.gnome.SessionManager",
+    methods: &[
+        Method::interactive(Action::Shutdown, "Shutdown", Args::None),
+        Method::interactive(Action::Reboot, "Reboot", Args::None),
+        // 1 - no confirmation dialog, 2 - force logout
+        Method::new(Action::Logout, "Logout", Args::Int(1)),
+    ],
+};
+
+const KDE: DBusBackend = DBusBackend {
+    bus: Bus::Session,
+    destination: "org.kde.ksmserver",
+    path: "/KSMServer",
+    interface: "org.kde.KSMServerInterface",
+    methods: &[
+        Method::interactive(Action::Shutdown, "logout", Args::Ints(-1, 2, 2)),
+        Method::interactive(Action::Reboot, "logout", Args::Ints(-1, 1, 2)),
+        Method::interactive(Action::Logout, "logout", Args::Ints(-1, 0, 2)),
+        Method::new(Action::Logout, "closeSession", Args::None),
+    ],
+};
+
+const XFCE: DBusBackend = DBusBackend {
+    bus: Bus::Session,
+    destination: "org.xfce.SessionManager",
+    path: "/org/xfce/SessionManager",
+    interface: "org.xfce.SessionManager",
+    methods: &[
+        // allow_save - true
+        Method::new(Action::Shutdown, "Shutdown", Args::Bool(true)),
+        Method::new(Action::Reboot, "Restart", Args::Bool(true)),
+        // show_dialog - true, allow_save - true
+        Method::interactive(Action::Logout, "Logout", Args::Bools(true, true)),
+        Method::new(Action::Sleep, "Suspend", Args::None),
+        Method::new(Action::Hibernate, "Hibernate", Args::None),
+    ],
+};
+
+const LOGIN1: DBusBackend = DBusBackend {
+    bus: Bus::System,
+    destination: "org.freedesktop.login1",
+    path: "/org/freedesktop/login1",
+    interface: "org.freedesktop.login1.Manager",
+    methods: &[
+        // interactive - true
+        Method::new(Action::Shutdown, "PowerOff", Args::Bool(true)),
+        Method::new(Action::Reboot, "Reboot", Args::Bool(true)),
+        Method::new(Action::Logout, "TerminateSession", Args::SessionId),
+        Method::new(Action::LockScreen, "LockSession", Args::SessionId),
+        Method::new(Action::Sleep, "Suspend", Args::Bool(true)),
+        Method::new(Action::Hibernate, "Hibernate", Args::Bool(true)),
+        Method::new(Action::HybridSleep, "HybridSleep", Args::Bool(true)),
+        Method::new(
+            Action::SuspendThenHibernate,
+            "SuspendThenHibernate",
+            Args::Bool(true),
+        ),
+        Method::new(Action::Halt, "Halt", Args::Bool(true)),
+        Method::new(
+            Action::KexecReboot,
+            "RebootWithFlags",
+            Args::Flags(SD_LOGIND_REBOOT_VIA_KEXEC),
+        ),
+        Method::new(
+            Action::SoftReboot,
+            "RebootWithFlags",
+            Args::Flags(SD_LOGIND_SOFT_REBOOT),
+        ),
+    ],
+};
+
+const POWER_MANAGEMENT: DBusBackend = DBusBackend {
+    bus: Bus::Session,
+    destination: "org.freedesktop.PowerManagement",
+    path: "/org/freedesktop/PowerManagement",
+    interface: "org.freedesktop.PowerManagement",
+    methods: &[
+        Method::new(Action::Shutdown, "Shutdown", Args::None),
+        Method::new(Action::Reboot, "Reboot", Args::None),
+    ],
+};
+
+const SESSION_MANAGEMENT: DBusBackend = DBusBackend {
+    bus: Bus::Session,
+    destination: "org.freedesktop.SessionManagement",
+    path: "/org/freedesktop/SessionManagement",
+    interface: "org.freedesktop.SessionManagement",
+    methods: &[
+        Method::new(Action::Shutdown, "Shutdown", Args::None),
+        Method::new(Action::Reboot, "Reboot", Args::None),
+    ],
+};
+
+const CONSOLE_KIT: DBusBackend = DBusBackend {
+    bus: Bus::System,
+    destination: "org.freedesktop.ConsoleKit",
+    path: "/org/freedesktop/ConsoleKit/Manager",
+    interface: "org.freedesktop.ConsoleKit.Manager",
+    methods: &[
+        Method::new(Action::Shutdown, "Stop", Args::None),
+        Method::new(Action::Reboot, "Restart", Args::None),
+        // interactive - true, available since ConsoleKit2
+        Method::new(Action::HybridSleep, "HybridSleep", Args::Bool(true)),
+        Method::new(
+            Action::SuspendThenHibernate,
+            "SuspendThenHibernate",
+            Args::Bool(true),
+        ),
+    ],
+};
+
+const UPOWER: DBusBackend = DBusBackend {
+    bus: Bus::System,
+    destination: "org.freedesktop.UPower",
+    path: "/org/freedesktop/UPower",
+    interface: "org.freedesktop.UPower",
+    methods: &[
+        Method::new(Action::Sleep, "Suspend", Args::None),
+        Method::new(Action::Hibernate, "Hibernate", Args::None),
+    ],
+};
+
+const HAL: DBusBackend = DBusBackend {
+    bus: Bus::System,
+    destination: "org.freedesktop.Hal",
+    path: "/org/freedesktop/Hal/devices/computer",
+    interface: "org.freedesktop.Hal.Device.SystemPowerManagement",
+    methods: &[
+        Method::new(Action::Shutdown, "Shutdown", Args::None),
+        Method::new(Action::Reboot, "Reboot", Args::None),
+        Method::new(Action::Sleep, "Suspend", Args::None),
+        Method::new(Action::Hibernate, "Hibernate", Args::None),
+    ],
+};
+
+const SYSTEMD: DBusBackend = DBusBackend {
+    bus: Bus::System,
+    destination: "org.freedesktop.systemd1",
+    path: "/org/freedesktop/systemd1",
+    interface: "org.freedesktop.systemd1.Manager",
+    methods: &[
+        Method::new(Action::Shutdown, "PowerOff", Args::None),
+        Method::new(Action::Reboot, "Reboot", Args::None),
+        Method::new(Action::Halt, "Halt", Args::None),
+        Method::new(Action::KexecReboot, "KExec", Args::None),
+        // new_root - none, keep the current root filesystem
+        Method::new(Action::SoftReboot, "SoftReboot", Args::Str("")),
+    ],
+};
+
+const GNOME_SCREEN_SAVER: DBusBackend = DBusBackend {
+    bus: Bus::Session,
+    destination: "org.gnome.ScreenSaver",
+    path: "/org/gnome/ScreenSaver",
+    interface: "org.gnome.ScreenSaver",
+    methods: &[Method::new(Action::LockScreen, "Lock", Args::None)],
+};
+
+const SCREEN_SAVER: DBusBackend = DBusBackend {
+    bus: Bus::Session,
+    destination: "org.freedesktop.ScreenSaver",
+    path: "/ScreenSaver",
+    interface: "org.freedesktop.ScreenSaver",
+    methods: &[Method::new(Action::LockScreen, "Lock", Args::None)],
+};
+
+/// KDE's screen locker, implementing the freedesktop interface under its own name.
+const KDE_SCREEN_SAVER: DBusBackend = DBusBackend {
+    destination: "org.kde.screensaver",
+    ..SCREEN_SAVER
+};
+
+/// Makes logind reboot into the kernel loaded with kexec.
+const SD_LOGIND_REBOOT_VIA_KEXEC: u64 = 1 << 1;
+
+/// Makes logind restart the userspace only, available since systemd 254.
+const SD_LOGIND_SOFT_REBOOT: u64 = 1 << 2;
+
+/// Makes logind ignore block inhibitors, available since systemd 256.
+const SD_LOGIND_SKIP_INHIBITORS: u64 = 1 << 4;
+
+/// The GNOME method logging out without asking for confirmation, used by [`force_logout`].
+const FORCE_GNOME: DBusBackend = DBusBackend {
+    // 2 - force logout
+    methods: &[Method::new(Action::Logout, "Logout", Args::Int(2))],
+    ..GNOME
+};
+
+/// The KDE method logging out without asking for confirmation, used by [`force_logout`].
+const FORCE_KDE: DBusBackend = DBusBackend {
+    // confirm - no, type - logout, mode - force now
+    methods: &[Method::new(Action::Logout, "logout", Args::Ints(0, 0, 2))],
+    ..KDE
+};
+
+/// The XFCE method logging out without asking for confirmation, used by [`force_logout`].
+const FORCE_XFCE: DBusBackend = DBusBackend {
+    // show_dialog - false, allow_save - false
+    methods: &[Method::new(
+        Action::Logout,
+        "Logout",
+        Args::Bools(false, false),
+    )],
+    ..XFCE
+};
+
+/// The logind methods powering off the machine or ending the session without asking for
+/// confirmation, used by [`force_shutdown`] and [`force_logout`].
+const FORCE_LOGIN1: DBusBackend = DBusBackend {
+    methods: &[
+        Method::new(
+            Action::Shutdown,
+            "PowerOffWithFlags",
+            Args::Flags(SD_LOGIND_SKIP_INHIBITORS),
+        ),
+        // interactive - false
+        Method::new(Action::Shutdown, "PowerOff", Args::Bool(false)),
+        Method::new(
+            Action::Logout,
+            "KillSession",
+            Args::SessionSignal("all", libc::SIGKILL),
+        ),
+        Method::new(Action::Logout, "TerminateSession", Args::SessionId),
+    ],
+    ..LOGIN1
+};
+
+/// The systemd method powering off the machine immediately, without stopping the services.
+const FORCE_SYSTEMD: DBusBackend = DBusBackend {
+    methods: &[Method::new(Action::Shutdown, "PowerOff", Args::None)],
+    ..SYSTEMD
+};
+
+/// The backends tried by [`force_shutdown`], in order.
+const FORCE_SHUTDOWN_BACKENDS: [DBusBackend; 2] = [FORCE_LOGIN1, FORCE_SYSTEMD];
+
+/// The backends tried by [`force_logout`], in order.
+const FORCE_LOGOUT_BACKENDS: [DBusBackend; 4] = [FORCE_GNOME, FORCE_KDE, FORCE_XFCE, FORCE_LOGIN1];
+
+/// A D-BUS method reporting whether an action is allowed.
+struct CapabilityQuery {
+    service: &'static DBusBackend,
+    method: &'static str,
+    /// Whether the method replies with `"yes"`, `"challenge"`, `"no"` or `"na"` instead of a boolean.
+    textual: bool,
+}
+
+impl CapabilityQuery {
+    const fn text(service: &'static DBusBackend, method: &'static str) -> Self {
+        CapabilityQuery {
+            service,
+            method,
+            textual: true,
+        }
+    }
+
+    const fn flag(service: &'static DBusBackend, method: &'static str) -> Self {
+        CapabilityQuery {
+            service,
+            method,
+            textual: false,
+        }
+    }
+
+    fn query(&self) -> Option<Capability> {
+        let s = self.service;
+        if self.textual {
+            dbus_query::<_, String>(s.bus, s.destination, s.path, s.interface, self.method, &())
+                .map(|reply| parse_capability(&reply))
+        } else {
+            dbus_query::<_, bool>(s.bus, s.destination, s.path, s.interface, self.method, &())
+                .map(Capability::from)
+        }
+    }
+}
+
+/// The queries answering each field of [`Capabilities`], tried in order until one gets a reply.
+/// The ConsoleKit textual methods are available since ConsoleKit2.
+struct CapabilityQueries {
+    shutdown: &'static [CapabilityQuery],
+    reboot: &'static [CapabilityQuery],
+    suspend: &'static [CapabilityQuery],
+    hibernate: &'static [CapabilityQuery],
+    hybrid_sleep: &'static [CapabilityQuery],
+    suspend_then_hibernate: &'static [CapabilityQuery],
+}
+
+const CAPABILITY_QUERIES: CapabilityQueries = CapabilityQueries {
+    shutdown: &[
+        CapabilityQuery::text(&LOGIN1, "CanPowerOff"),
+        CapabilityQuery::text(&CONSOLE_KIT, "CanPowerOff"),
+        CapabilityQuery::flag(&CONSOLE_KIT, "CanStop"),
+    ],
+    reboot: &[
+        CapabilityQuery::text(&LOGIN1, "CanReboot"),
+        CapabilityQuery::text(&CONSOLE_KIT, "CanReboot"),
+        CapabilityQuery::flag(&CONSOLE_KIT, "CanRestart"),
+    ],
+    suspend: &[
+        CapabilityQuery::text(&LOGIN1, "CanSuspend"),
+        CapabilityQuery::text(&CONSOLE_KIT, "CanSuspend"),
+        CapabilityQuery::flag(&UPOWER, "SuspendAllowed"),
+    ],
+    hibernate: &[
+        CapabilityQuery::text(&LOGIN1, "CanHibernate"),
+        CapabilityQuery::text(&CONSOLE_KIT, "CanHibernate"),
+        CapabilityQuery::flag(&UPOWER, "HibernateAllowed"),
+    ],
+    hybrid_sleep: &[
+        CapabilityQuery::text(&LOGIN1, "CanHybridSleep"),
+        CapabilityQuery::text(&CONSOLE_KIT, "CanHybridSleep"),
+    ],
+    suspend_then_hibernate: &[
+        CapabilityQuery::text(&LOGIN1, "CanSuspendThenHibernate"),
+        CapabilityQuery::text(&CONSOLE_KIT, "CanSuspendThenHibernate"),
+    ],
+};
+
+/// Answers with the first of `queries` that gets a reply, or [`Capability::Unknown`].
+fn query_first(queries: &[CapabilityQuery]) -> Capability {
+    queries
+        .iter()
+        .find_map(CapabilityQuery::query)
+        .unwrap_or(Capability::Unknown)
+}
+
+pub(crate) fn builtin_backends() -> Vec<Arc<dyn PowerBackend>> {
+    let mut backends: Vec<Arc<dyn PowerBackend>> = [
+        GNOME,
+        KDE,
+        XFCE,
+        LOGIN1,
+        POWER_MANAGEMENT,
+        SESSION_MANAGEMENT,
+        CONSOLE_KIT,
+        UPOWER,
+        HAL,
+        SYSTEMD,
+        GNOME_SCREEN_SAVER,
+        SCREEN_SAVER,
+        KDE_SCREEN_SAVER,
+    ]
+    .into_iter()
+    .map(|backend| Arc::new(backend) as Arc<dyn PowerBackend>)
+    .collect();
+    backends.push(Arc::new(CommandBackend));
+    backends
+}
+
+/// Linux specific function listing the backends of the current [`registry`] that would be tried
+/// for `action`, probing each D-BUS name with `NameHasOwner` to report whether it is available.
+pub fn plan(action: Action) -> Vec<Step> {
+    registry().plan(action)
+}
+
+/// The D-BUS backends the force variant of `action` tries, then its last resort.
+fn force_backends(action: Action) -> (&'static [DBusBackend], Option<Step>) {
+    let native = |backend: &str, calls: &[&str]| Step {
+        backend: backend.to_string(),
+        available: true,
+        invocations: calls
+            .iter()
+            .map(|call| Invocation::Native(call.to_string()))
+            .collect(),
+    };
+    let sysrq_trigger = "/proc/sysrq-trigger";
+    match action {
+        Action::Shutdown => (
+            &FORCE_SHUTDOWN_BACKENDS,
+            Some(native("sysrq", &["sync(2)", sysrq_trigger])),
+        ),
+        Action::Reboot => (&[], Some(native("sysrq", &[sysrq_trigger]))),
+        Action::Logout => (
+            &FORCE_LOGOUT_BACKENDS,
+            Some(native("kill", &["kill(SIGKILL) on the session processes"])),
+        ),
+        _ => (&[], None),
+    }
+}
+
+/// Linux specific function listing the steps [`crate::force_shutdown`], [`crate::force_reboot`]
+/// or [`crate::force_logout`] would take for `action`, probing each D-BUS name like
+/// [`crate::plan`]. Empty for the other actions.
+pub fn plan_force(action: Action) -> Vec<Step> {
+    let (backends, last_resort) = force_backends(action);
+    backends
+        .iter()
+        .filter(|backend| backend.supports(action))
+        .map(|backend| Step {
+            backend: backend.name().to_string(),
+            available: backend.probe(),
+            invocations: backend.invocations(action),
+        })
+        .chain(last_resort)
+        .collect()
+}
+
+/// Options changing how [`perform_with`] performs an action.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct ActionOptions {
+    /// Fail with [`ShutdownError::Inhibited`] before trying any backend if a lock blocks the
+    /// action, listing the applications holding it. The action is not performed either if logind
+    /// is running but the locks cannot be listed.
+    pub fail_if_inhibited: bool,
+    /// Lock the screen with [`crate::lock_screen`] before putting the machine to sleep or
+    /// hibernating it. The action is not performed if the screen cannot be locked.
+    pub lock_before_sleep: bool,
+}
+
+impl ActionOptions {
+    /// Creates the default options.
+    pub fn new() -> Self {
+        ActionOptions::default()
+    }
+
+    /// Sets [`ActionOptions::fail_if_inhibited`].
+    pub fn fail_if_inhibited(mut self, fail_if_inhibited: bool) -> Self {
+        self.fail_if_inhibited = fail_if_inhibited;
+        self
+    }
+
+    /// Sets [`ActionOptions::lock_before_sleep`].
+    pub fn lock_before_sleep(mut self, lock_before_sleep: bool) -> Self {
+        self.lock_before_sleep = lock_before_sleep;
+        self
+    }
+}
+
+/// Linux specific function performing `action` with the backends of the current [`registry`],
+/// like [`crate::shutdown`], [`crate::reboot`] or [`crate::sleep`] do, according to `options`.
+pub fn perform_with(action: Action, options: ActionOptions) -> ShutdownResult {
+    if let Some(target) = inhibit_target(action).filter(|_| options.fail_if_inhibited) {
+        check_inhibitors(target, list_inhibitors())?;
+    }
+    if options.lock_before_sleep && is_sleep(action) {
+        lock_screen()?;
+    }
+    registry().perform(action)
+}
+
+/// The inhibitor locks that can block `action`, if any.
+fn inhibit_target(action: Action) -> Option<InhibitTarget> {
+    match action {
+        Action::Shutdown
+        | Action::Reboot
+        | Action::KexecReboot
+        | Action::SoftReboot
+        | Action::Halt => Some(InhibitTarget::Shutdown),
+        action if is_sleep(action) => Some(InhibitTarget::Sleep),
+        _ => None,
+    }
+}
+
+/// Fails with [`ShutdownError::Inhibited`] if one of the listed `locks` blocks `target`.
+/// Inhibitors cannot be listed without logind, in which case nothing is checked, but any other
+/// failure to list them is returned.
+fn check_inhibitors(
+    target: InhibitTarget,
+    locks: ShutdownResult<Vec<InhibitorLock>>,
+) -> ShutdownResult<()> {
+    let locks = match locks {
+        Ok(locks) => locks,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(error) => return Err(error),
+    };
+    let blockers = locks
+        .into_iter()
+        .filter(|lock| lock.holds(target) && lock.is(InhibitMode::Block))
+        .map(|lock| {
+            let reason = format!("{} (pid {}, uid {})", lock.why, lock.pid, lock.uid);
+            Attempt::new(lock.who, reason, ErrorKind::ResourceBusy)
+        })
+        .collect::<Vec<_>>();
+    if blockers.is_empty() {
+        Ok(())
+    } else {
+        Err(ShutdownError::Inhibited(blockers))
+    }
+}
+
+/// Whether `action` puts the machine to sleep or hibernates it.
+fn is_sleep(action: Action) -> bool {
+    matches!(
+        action,
+        Action::Sleep | Action::Hibernate | Action::HybridSleep | Action::SuspendThenHibernate
+    )
+}
+
+/// Parses the `"yes"`, `"challenge"`, `"no"` and `"na"` replies of logind and ConsoleKit2.
+fn parse_capability(reply: &str) -> Capability {
+    match reply {
+        "yes" => Capability::Yes,
+        "challenge" => Capability::Challenge,
+        "no" => Capability::No,
+        "na" => Capability::NotApplicable,
+        _ => Capability::Unknown,
+    }
+}
+
+/// Linux specific function querying which power actions are allowed.
+/// The following D-BUS calls are attempted for each action, in order:
+/// - org.freedesktop.login1.Manager.CanPowerOff(), CanReboot(), CanSuspend(), CanHibernate(),
+///   CanHybridSleep() and CanSuspendThenHibernate()
+/// - org.freedesktop.ConsoleKit.Manager.CanPowerOff(), CanReboot(), CanSuspend(), CanHibernate(),
+///   CanHybridSleep() and CanSuspendThenHibernate(), available since ConsoleKit2
+/// - org.freedesktop.ConsoleKit.Manager.CanStop() and CanRestart()
+/// - org.freedesktop.UPower.SuspendAllowed() and HibernateAllowed()
+///
+/// Rebooting with kexec is possible if `/sys/kernel/kexec_loaded` reports a loaded kernel.
+///
+/// Actions none of them reports on are [`Capability::Unknown`].
+pub fn capabilities() -> Capabilities {
+    let queries = &CAPABILITY_QUERIES;
+    let reboot = query_first(queries.reboot);
+    Capabilities {
+        shutdown: query_first(queries.shutdown),
+        reboot,
+        suspend: query_first(queries.suspend),
+        hibernate: query_first(queries.hibernate),
+        hybrid_sleep: query_first(queries.hybrid_sleep),
+        suspend_then_hibernate: query_first(queries.suspend_then_hibernate),
+        kexec_reboot: kexec_capability(reboot),
+    }
+}
+
+/// Linux specific function checking whether the machine can be shut down, running only the
+/// shut down queries of [`capabilities`].
+pub fn can_shutdown() -> Capability {
+    query_first(CAPABILITY_QUERIES.shutdown)
+}
+
+/// Linux specific function checking whether the machine can be rebooted, running only the reboot
+/// queries of [`capabilities`].
+pub fn can_reboot() -> Capability {
+    query_first(CAPABILITY_QUERIES.reboot)
+}
+
+/// Linux specific function checking whether the machine can be put to sleep, running only the
+/// suspend queries of [`capabilities`].
+pub fn can_suspend() -> Capability {
+    query_first(CAPABILITY_QUERIES.suspend)
+}
+
+/// Linux specific function checking whether the machine can be hibernated, running only the
+/// hibernate queries of [`capabilities`].
+pub fn can_hibernate() -> Capability {
+    query_first(CAPABILITY_QUERIES.hibernate)
+}
+
+/// Reports whether a kernel is loaded with kexec, from `/sys/kernel/kexec_loaded`. Rebooting
+/// into it needs the same permission as `reboot`.
+fn kexec_capability(reboot: Capability) -> Capability {
+    match std::fs::read_to_string("/sys/kernel/kexec_loaded") {
+        Ok(loaded) if loaded.trim() == "1" => reboot,
+        Ok(_) => Capability::NotApplicable,
+        Err(_) => Capability::Unknown,
+    }
+}
+
 /// Linux specific function to shut down the machine using D-BUS method call.
 /// The following D-BUS calls are attempted:
 /// - org.gnome.SessionManager.Shutdown()
@@ -88,97 +1276,30 @@ fn get_session_id() -> String {
 /// - org.freedesktop.ConsoleKit.Manager.Stop()
 /// - org.freedesktop.Hal.Device.SystemPowerManagement.Shutdown()
 /// - org.freedesktop.systemd1.Manager.PowerOff()
-/// If nothing works up to this point, as a last resort this function calls `shutdown -h now`
+///
+/// If nothing works up to this point, as a last resort this function calls `shutdown -h now`.
+/// If the user cancels the confirmation dialog, [`ShutdownError::Cancelled`] is returned.
 pub fn shutdown() -> ShutdownResult {
-    if dbus_send(
-        "org.gnome.SessionManager",
-        "/org/gnome/SessionManager",
-        "org.gnome.SessionManager",
-        "Shutdown",
-        &(),
-    ) {
-        return Ok(());
-    }
-    if dbus_send(
-        "org.kde.ksmserver",
-        "/KSMServer",
-        "org.kde.KSMServerInterface",
-        "logout",
-        &(-1, 2, 2),
-    ) {
-        return Ok(());
-    }
-    if dbus_send(
-        "org.xfce.SessionManager",
-        "/org/xfce/SessionManager",
-        "org.xfce.SessionManager",
-        "Shutdown",
-        &(true),
-    ) {
-        return Ok(());
-    } // allow_save - true
-    if dbus_send(
-        "org.freedesktop.login1",
-        "/org/freedesktop/login1",
-        "org.freedesktop.login1.Manager",
-        "PowerOff",
-        &(true),
-    ) {
-        return Ok(());
-    } // interactive - true
-    if dbus_send(
-        "org.freedesktop.PowerManagement",
-        "/org/freedesktop/PowerManagement",
-        "org.freedesktop.PowerManagement",
-        "Shutdown",
-        &(),
-    ) {
-        return Ok(());
-    }
-    if dbus_send(
-        "org.freedesktop.SessionManagement",
-        "/org/freedesktop/SessionManagement",
-        "org.freedesktop.SessionManagement",
-        "Shutdown",
-        &(),
-    ) {
-        return Ok(());
-    }
-    if dbus_send(
-        "org.freedesktop.ConsoleKit",
-        "/org/freedesktop/ConsoleKit/Manager",
-        "org.freedesktop.ConsoleKit.Manager",
-        "Stop",
-        &(),
-    ) {
-        return Ok(());
-    }
-    if dbus_send(
-        "org.freedesktop.Hal",
-        "/org/freedesktop/Hal/devices/computer",
-        "org.freedesktop.Hal.Device.SystemPowerManagement",
-        "Shutdown",
-        &(),
-    ) {
-        return Ok(());
-    }
-    if dbus_send(
-        "org.freedesktop.systemd1",
-        "/org/freedesktop/systemd1",
-        "org.freedesktop.systemd1.Manager",
-        "PowerOff",
-        &(),
-    ) {
-        return Ok(());
-    }
-
-    // As a last resort
-    run_command("shutdown", &["-h", "now"])
-}
-
-#[doc(hidden)]
+    registry().perform(Action::Shutdown)
+}
+
+/// Linux specific function to force to shut down the machine, ignoring inhibitors.
+/// The following D-BUS calls are attempted:
+/// - org.freedesktop.login1.Manager.PowerOffWithFlags(SD_LOGIND_SKIP_INHIBITORS)
+/// - org.freedesktop.login1.Manager.PowerOff(false)
+/// - org.freedesktop.systemd1.Manager.PowerOff()
+///
+/// If nothing works up to this point, as a last resort this function flushes the filesystems with
+/// `sync(2)` and powers off the machine using the magic SysRq key.
 pub fn force_shutdown() -> ShutdownResult {
-    not_implemented!()
+    let mut attempts = Vec::new();
+    let backends = &FORCE_SHUTDOWN_BACKENDS;
+    if let Some(outcome) = perform_each(&mut attempts, backends, Action::Shutdown) {
+        return Ok(outcome);
+    }
+    // SAFETY: sync(2) has no memory safety requirements.
+    unsafe { libc::sync() };
+    after_attempts(attempts, SysRq::new().perform(b'o'))
 }
 
 /// Linux specific function to reboot the machine using D-BUS method call.
@@ -192,102 +1313,62 @@ pub fn force_shutdown() -> ShutdownResult {
 /// - org.freedesktop.ConsoleKit.Manager.Restart()
 /// - org.freedesktop.Hal.Device.SystemPowerManagement.Reboot()
 /// - org.freedesktop.systemd1.Manager.Reboot()
-/// If nothing works up to this point, as a last resort this function calls `shutdown -r now`
+///
+/// If nothing works up to this point, as a last resort this function calls `shutdown -r now`.
+/// If the user cancels the confirmation dialog, [`ShutdownError::Cancelled`] is returned.
 pub fn reboot() -> ShutdownResult {
-    if dbus_send(
-        "org.gnome.SessionManager",
-        "/org/gnome/SessionManager",
-        "org.gnome.SessionManager",
-        "Reboot",
-        &(),
-    ) {
-        return Ok(());
-    }
-    if dbus_send(
-        "org.kde.ksmserver",
-        "/KSMServer",
-        "org.kde.KSMServerInterface",
-        "logout",
-        &(-1, 1, 2),
-    ) {
-        return Ok(());
-    }
-    if dbus_send(
-        "org.xfce.SessionManager",
-        "/org/xfce/SessionManager",
-        "org.xfce.SessionManager",
-        "Restart",
-        &(true),
-    ) {
-        return Ok(());
-    } // allow_save - true
-    if dbus_send(
-        "org.freedesktop.login1",
-        "/org/freedesktop/login1",
-        "org.freedesktop.login1.Manager",
-        "Reboot",
-        &(true),
-    ) {
-        return Ok(());
-    } // interactive - true
-    if dbus_send(
-        "org.freedesktop.PowerManagement",
-        "/org/freedesktop/PowerManagement",
-        "org.freedesktop.PowerManagement",
-        "Reboot",
-        &(),
-    ) {
-        return Ok(());
-    }
-    if dbus_send(
-        "org.freedesktop.SessionManagement",
-        "/org/freedesktop/SessionManagement",
-        "org.freedesktop.SessionManagement",
-        "Reboot",
-        &(),
-    ) {
-        return Ok(());
-    }
-    if dbus_send(
-        "org.freedesktop.ConsoleKit",
-        "/org/freedesktop/ConsoleKit/Manager",
-        "org.freedesktop.ConsoleKit.Manager",
-        "Restart",
-        &(),
-    ) {
-        return Ok(());
-    }
-    if dbus_send(
-        "org.freedesktop.Hal",
-        "/org/freedesktop/Hal/devices/computer",
-        "org.freedesktop.Hal.Device.SystemPowerManagement",
-        "Reboot",
-        &(),
-    ) {
-        return Ok(());
-    }
-    if dbus_send(
-        "org.freedesktop.systemd1",
-        "/org/freedesktop/systemd1",
-        "org.freedesktop.systemd1.Manager",
-        "Reboot",
-        &(),
-    ) {
-        return Ok(());
-    }
-
-    // As a last resort
-    run_command("shutdown", &["-r", "now"])
+    registry().perform(Action::Reboot)
 }
 
 /// Linux specific function to force reboot the machine using the magic SysRq key.
+/// The machine reboots immediately, without syncing or unmounting the filesystems, see
+/// [`EmergencyReboot`] for a safer sequence.
+/// The reboot function is enabled in `kernel.sysrq` if needed, and its original value is restored
+/// if the reboot fails.
 /// Reference: https://www.kernel.org/doc/html/latest/admin-guide/sysrq.html
 pub fn force_reboot() -> ShutdownResult {
-    let mut file = File::create("/proc/sys/kernel/sysrq")?;
-    file.write_all(b"128")?;
-    file = File::create("/proc/sysrq-trigger")?;
-    file.write_all(b"b")?;
-    Ok(())
+    SysRq::new().perform(b'b')
+}
+
+/// Calls `method` on logind with `body`, running `command` as a last resort if it fails.
+fn login1_or_command<B: Serialize + DynamicType>(
+    method: &str,
+    body: &B,
+    command: &[&str],
+) -> ShutdownResult {
+    let mut attempts = Vec::new();
+    let sent = LOGIN1.send(&mut attempts, method, body);
+    login1_result(method, sent, &mut attempts)
+        .unwrap_or_else(|| after_attempts(attempts, run_command(command[0], &command[1..])))
+}
+
+/// Turns the result of calling `method` of logind into the result of the action, or `None` if
+/// the fallback command is to be run.
+fn login1_result(method: &str, sent: bool, attempts: &mut Vec<Attempt>) -> Option<ShutdownResult> {
+    if sent {
+        let invocation = LOGIN1.invocation(method);
+        return Some(Ok(Outcome::new(
+            LOGIN1.destination,
+            invocation,
+            Status::Completed,
+        )));
+    }
+    is_cancelled(attempts).then(|| Err(ShutdownError::from_attempts(mem::take(attempts))))
+}
+
+/// Tries each of `backends` in order, recording their failures, until one of them performs `action`.
+fn perform_each(
+    attempts: &mut Vec<Attempt>,
+    backends: &[DBusBackend],
+    action: Action,
+) -> Option<Outcome> {
+    for backend in backends {
+        match backend.perform(action) {
+            Ok(outcome) => return Some(outcome),
+            Err(error) => attempts.extend_from_slice(error.attempts()),
+        }
+    }
+    None
 }
 
 /// Linux specific function to log out the user using D-BUS method call.
@@ -297,70 +1378,30 @@ pub fn force_reboot() -> ShutdownResult {
 /// - org.kde.KSMServerInterface.closeSession()
 /// - org.xfce.SessionManager.Logout(true, true)
 /// - org.freedesktop.login1.Manager.TerminateSession(session_id)
-/// If nothing works up to this point, as a last resort this function calls `loginctl kill-session $XDG_SESSION_ID`
+///
+/// If nothing works up to this point, as a last resort this function calls `loginctl kill-session $XDG_SESSION_ID`.
+/// If the user cancels the confirmation dialog, [`ShutdownError::Cancelled`] is returned.
 pub fn logout() -> ShutdownResult {
-    if dbus_send(
-        "org.gnome.SessionManager",
-        "/org/gnome/SessionManager",
-        "org.gnome.SessionManager",
-        "Logout",
-        &(1),
-    ) {
-        return Ok(());
-    } // 1 - no confirmation dialog, 2 - force logout
-    if dbus_send(
-        "org.kde.ksmserver",
-        "/KSMServer",
-        "org.kde.KSMServerInterface",
-        "logout",
-        &(-1, 0, 2),
-    ) {
-        return Ok(());
-    }
-    if dbus_send(
-        "org.kde.ksmserver",
-        "/KSMServer",
-        "org.kde.KSMServerInterface",
-        "closeSession",
-        &(),
-    ) {
-        return Ok(());
-    }
-    if dbus_send(
-        "org.xfce.SessionManager",
-        "/org/xfce/SessionManager",
-        "org.xfce.SessionManager",
-        "Logout",
-        &(true, true),
-    ) {
-        return Ok(());
-    } // show_dialog - true, allow_save - true
-
-    let session_id = get_session_id();
-    if session_id.is_empty() {
-        return Err(Error::new(
-            ErrorKind::Other,
-            "could not determine session ID for logout",
-        ));
-    }
-
-    if dbus_send(
-        "org.freedesktop.login1",
-        "/org/freedesktop/login1",
-        "org.freedesktop.login1.Manager",
-        "TerminateSession",
-        &session_id,
-    ) {
-        return Ok(());
-    }
-
-    // As a last resort
-    run_command("loginctl", &["kill-session", &session_id])
+    registry().perform(Action::Logout)
 }
 
-#[doc(hidden)]
+/// Linux specific function to force to log out the user, without asking for confirmation.
+/// The following D-BUS calls are attempted:
+/// - org.gnome.SessionManager.Logout(2)
+/// - org.kde.KSMServerInterface.logout(0, 0, 2)
+/// - org.xfce.SessionManager.Logout(false, false)
+/// - org.freedesktop.login1.Manager.KillSession(session_id, "all", SIGKILL)
+/// - org.freedesktop.login1.Manager.TerminateSession(session_id)
+///
+/// If nothing works up to this point, as a last resort this function sends `SIGKILL` to the
+/// processes of the session.
 pub fn force_logout() -> ShutdownResult {
-    not_implemented!()
+    let mut attempts = Vec::new();
+    let backends = &FORCE_LOGOUT_BACKENDS;
+    if let Some(outcome) = perform_each(&mut attempts, backends, Action::Logout) {
+        return Ok(outcome);
+    }
+    after_attempts(attempts, kill_session())
 }
 
 /// Linux specific function to put the machine to sleep using D-BUS method call.
@@ -369,47 +1410,10 @@ pub fn force_logout() -> ShutdownResult {
 /// - org.freedesktop.login1.Manager.Suspend(true)
 /// - org.freedesktop.UPower.Suspend()
 /// - org.freedesktop.Hal.Device.SystemPowerManagement.Suspend()
+///
 /// If nothing works up to this point, as a last resort this function calls `systemctl suspend`
 pub fn sleep() -> ShutdownResult {
-    if dbus_send(
-        "org.xfce.SessionManager",
-        "/org/xfce/SessionManager",
-        "org.xfce.SessionManager",
-        "Suspend",
-        &(),
-    ) {
-        return Ok(());
-    }
-    if dbus_send(
-        "org.freedesktop.login1",
-        "/org/freedesktop/login1",
-        "org.freedesktop.login1.Manager",
-        "Suspend",
-        &(true),
-    ) {
-        return Ok(());
-    } // interactive - true
-    if dbus_send(
-        "org.freedesktop.UPower",
-        "/org/freedesktop/UPower",
-        "org.freedesktop.UPower",
-        "Suspend",
-        &(),
-    ) {
-        return Ok(());
-    }
-    if dbus_send(
-        "org.freedesktop.Hal",
-        "/org/freedesktop/Hal/devices/computer",
-        "org.freedesktop.Hal.Device.SystemPowerManagement",
-        "Suspend",
-        &(),
-    ) {
-        return Ok(());
-    }
-
-    // As a last resort
-    run_command("systemctl", &["suspend"])
+    registry().perform(Action::Sleep)
 }
 
 /// Linux specific function to hibernate the machine using D-BUS method call.
@@ -418,45 +1422,216 @@ pub fn sleep() -> ShutdownResult {
 /// - org.freedesktop.login1.Manager.Hibernate(true)
 /// - org.freedesktop.UPower.Hibernate()
 /// - org.freedesktop.Hal.Device.SystemPowerManagement.Hibernate()
+///
 /// If nothing works up to this point, as a last resort this function calls `systemctl hibernate`
 pub fn hibernate() -> ShutdownResult {
-    if dbus_send(
-        "org.xfce.SessionManager",
-        "/org/xfce/SessionManager",
-        "org.xfce.SessionManager",
-        "Hibernate",
-        &(),
-    ) {
-        return Ok(());
-    }
-    if dbus_send(
-        "org.freedesktop.login1",
-        "/org/freedesktop/login1",
-        "org.freedesktop.login1.Manager",
-        "Hibernate",
-        &(true),
-    ) {
-        return Ok(());
-    } // interactive - true
-    if dbus_send(
-        "org.freedesktop.UPower",
-        "/org/freedesktop/UPower",
-        "org.freedesktop.UPower",
-        "Hibernate",
-        &(),
-    ) {
-        return Ok(());
-    }
-    if dbus_send(
-        "org.freedesktop.Hal",
-        "/org/freedesktop/Hal/devices/computer",
-        "org.freedesktop.Hal.Device.SystemPowerManagement",
-        "Hibernate",
-        &(),
-    ) {
-        return Ok(());
-    }
-
-    // As a last resort
-    run_command("systemctl", &["hibernate"])
+    registry().perform(Action::Hibernate)
+}
+
+/// Linux specific function to suspend the machine to both RAM and disk using D-BUS method call.
+/// The following D-BUS calls are attempted:
+/// - org.freedesktop.login1.Manager.HybridSleep(true)
+/// - org.freedesktop.ConsoleKit.Manager.HybridSleep(true)
+///
+/// If nothing works up to this point, as a last resort this function calls `systemctl hybrid-sleep`
+pub fn hybrid_sleep() -> ShutdownResult {
+    registry().perform(Action::HybridSleep)
+}
+
+/// Linux specific function to put the machine to sleep, then hibernate it after a while, using
+/// D-BUS method call.
+/// The following D-BUS calls are attempted:
+/// - org.freedesktop.login1.Manager.SuspendThenHibernate(true)
+/// - org.freedesktop.ConsoleKit.Manager.SuspendThenHibernate(true)
+///
+/// If nothing works up to this point, as a last resort this function calls
+/// `systemctl suspend-then-hibernate`
+pub fn suspend_then_hibernate() -> ShutdownResult {
+    registry().perform(Action::SuspendThenHibernate)
+}
+
+/// Linux specific function to lock the screen of the current session using D-BUS method call.
+/// The following D-BUS calls are attempted:
+/// - org.freedesktop.login1.Manager.LockSession(session_id)
+/// - org.gnome.ScreenSaver.Lock()
+/// - org.freedesktop.ScreenSaver.Lock()
+/// - org.freedesktop.ScreenSaver.Lock() on org.kde.screensaver
+///
+/// If nothing works up to this point, as a last resort this function calls `xdg-screensaver lock`
+pub fn lock_screen() -> ShutdownResult {
+    registry().perform(Action::LockScreen)
+}
+
+/// Linux specific function to halt the machine without cutting the power, using D-BUS method call.
+/// The following D-BUS calls are attempted:
+/// - org.freedesktop.login1.Manager.Halt(true)
+/// - org.freedesktop.systemd1.Manager.Halt()
+///
+/// If nothing works up to this point, as a last resort this function calls `shutdown -H now`,
+/// then `halt`.
+pub fn halt() -> ShutdownResult {
+    registry().perform(Action::Halt)
+}
+
+/// Linux specific function to reboot the machine into the kernel loaded with kexec, using D-BUS
+/// method call.
+/// The following D-BUS calls are attempted:
+/// - org.freedesktop.login1.Manager.RebootWithFlags(SD_LOGIND_REBOOT_VIA_KEXEC)
+/// - org.freedesktop.systemd1.Manager.KExec()
+///
+/// If nothing works up to this point, as a last resort this function calls `systemctl kexec`
+pub fn kexec_reboot() -> ShutdownResult {
+    registry().perform(Action::KexecReboot)
+}
+
+/// Linux specific function to restart the userspace only, keeping the running kernel, using D-BUS
+/// method call.
+/// The following D-BUS calls are attempted:
+/// - org.freedesktop.login1.Manager.RebootWithFlags(SD_LOGIND_SOFT_REBOOT)
+/// - org.freedesktop.systemd1.Manager.SoftReboot("")
+///
+/// If nothing works up to this point, as a last resort this function calls `systemctl soft-reboot`
+pub fn soft_reboot() -> ShutdownResult {
+    registry().perform(Action::SoftReboot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The signature and the serialized bytes of a method call body.
+    fn encode<B: Serialize + DynamicType>(body: &B) -> (String, Vec<u8>) {
+        let message = Message::method_call("/", "Test")
+            .unwrap()
+            .build(body)
+            .unwrap();
+        let body = message.body();
+        (body.signature().to_string(), body.data().to_vec())
+    }
+
+    #[test]
+    fn dbus_error_kind_classifies_the_error_names() {
+        let cases = [
+            (
+                "org.freedesktop.DBus.Error.AccessDenied",
+                ErrorKind::PermissionDenied,
+            ),
+            (
+                "org.freedesktop.DBus.Error.AuthFailed",
+                ErrorKind::PermissionDenied,
+            ),
+            (
+                "org.freedesktop.DBus.Error.InteractiveAuthorizationRequired",
+                ErrorKind::PermissionDenied,
+            ),
+            (
+                "org.freedesktop.PolicyKit1.Error.NotAuthorized",
+                ErrorKind::PermissionDenied,
+            ),
+            (
+                "org.freedesktop.DBus.Error.NotSupported",
+                ErrorKind::Unsupported,
+            ),
+            (
+                "org.freedesktop.DBus.Error.UnknownMethod",
+                ErrorKind::Unsupported,
+            ),
+            (
+                "org.freedesktop.DBus.Error.UnknownObject",
+                ErrorKind::Unsupported,
+            ),
+            (
+                "org.freedesktop.DBus.Error.UnknownInterface",
+                ErrorKind::Unsupported,
+            ),
+            (
+                "org.freedesktop.login1.SleepVerbNotSupported",
+                ErrorKind::Unsupported,
+            ),
+            (
+                "org.freedesktop.DBus.Error.ServiceUnknown",
+                ErrorKind::NotFound,
+            ),
+            (
+                "org.freedesktop.DBus.Error.NameHasNoOwner",
+                ErrorKind::NotFound,
+            ),
+            (
+                "org.freedesktop.PolicyKit1.Error.Cancelled",
+                ErrorKind::Interrupted,
+            ),
+            (
+                "org.gtk.GDBus.UnmappedGError.Quark._g_2dio_2derror_2dquark.Code19",
+                ErrorKind::Interrupted,
+            ),
+            (
+                "org.gtk.GDBus.UnmappedGError.Quark._g_2dio_2derror_2dquark.Code1",
+                ErrorKind::Other,
+            ),
+            (
+                "org.gtk.GDBus.UnmappedGError.Quark._g_2dio_2derror_2dquark.Code190",
+                ErrorKind::Other,
+            ),
+            (
+                "org.freedesktop.login1.BlockedByInhibitorLock",
+                ErrorKind::ResourceBusy,
+            ),
+            (
+                "org.freedesktop.login1.OperationInhibited",
+                ErrorKind::ResourceBusy,
+            ),
+            ("org.freedesktop.DBus.Error.Failed", ErrorKind::Other),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(dbus_error_kind(name), expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn body_is_encoded_like_the_plain_arguments() {
+        let session = || "c1".to_string();
+        assert_eq!(encode(&Body::None), encode(&()));
+        assert_eq!(encode(&Body::Bool(true)), encode(&(true)));
+        assert_eq!(encode(&Body::Bools(false, true)), encode(&(false, true)));
+        assert_eq!(encode(&Body::Int(2)), encode(&(2i32)));
+        assert_eq!(encode(&Body::Ints(-1, 2, 2)), encode(&(-1i32, 2i32, 2i32)));
+        assert_eq!(encode(&Body::Flags(1 << 4)), encode(&(1u64 << 4)));
+        assert_eq!(encode(&Body::Str("")), encode(&("")));
+        assert_eq!(encode(&Body::SessionId(session())), encode(&(session())));
+        assert_eq!(
+            encode(&Body::SessionSignal(session(), "all", libc::SIGKILL)),
+            encode(&(session(), "all", libc::SIGKILL))
+        );
+    }
+
+    #[test]
+    fn check_inhibitors_fails_only_for_blocking_locks_or_unlisted_locks() {
+        let lock = |what: &str, mode: &str| {
+            let entry = (
+                what.into(),
+                "app".into(),
+                "busy".into(),
+                mode.into(),
+                1000,
+                42,
+            );
+            InhibitorLock::new(entry)
+        };
+        let failure = |kind| ShutdownError::from_attempts(vec![Attempt::new("login1", "", kind)]);
+        let shutdown = InhibitTarget::Shutdown;
+        let locks = vec![lock("sleep:shutdown", "block")];
+        let error = check_inhibitors(shutdown, Ok(locks)).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::ResourceBusy);
+        assert_eq!(error.attempts()[0].backend, "app");
+        let locks = vec![lock("shutdown", "delay"), lock("sleep", "block")];
+        assert!(check_inhibitors(shutdown, Ok(locks)).is_ok());
+        assert!(check_inhibitors(shutdown, Err(failure(ErrorKind::NotFound))).is_ok());
+        let error = check_inhibitors(shutdown, Err(failure(ErrorKind::PermissionDenied)));
+        assert_eq!(error.unwrap_err().kind(), ErrorKind::PermissionDenied);
+        assert_eq!(inhibit_target(Action::LockScreen), None);
+        assert_eq!(
+            inhibit_target(Action::Hibernate),
+            Some(InhibitTarget::Sleep)
+        );
+    }
 }
@@ -0,0 +1,686 @@
+//! Async versions of the power actions, built on the async zbus connection.
+//!
+//! Available with the `async` cargo feature. These functions do not block the calling task and
+//! work with any executor.
+
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::time::{Duration, SystemTime};
+
+use async_process::Command;
+use zbus::export::serde::Serialize;
+use zbus::export::serde::de::DeserializeOwned;
+use zbus::zvariant::{DynamicType, OwnedObjectPath, OwnedValue, Type};
+use zbus::{Connection, Message, Proxy};
+
+use super::boot::{RebootTarget, boot_entries_unavailable};
+use super::inhibit::{InhibitorEntry, InhibitorLock};
+use super::schedule::{self, ScheduledShutdown};
+use super::session::{
+    LoginSession, LoginUser, PROPERTIES_INTERFACE, SESSION_INTERFACE, SessionEntry, USER_INTERFACE,
+    UserEntry,
+};
+use super::sysrq::SysRq;
+use super::{
+    ActionOptions, Args, Bus, CAPABILITY_QUERIES, CapabilityQuery, CommandBackend, DBusBackend,
+    FORCE_LOGOUT_BACKENDS, FORCE_SHUTDOWN_BACKENDS, LOGIN1, after_attempts, check_inhibitors,
+    check_reply, command_result, force_backends, inhibit_target, is_sleep, kexec_capability,
+    kill_session, login1_result, parse_capability,
+};
+use crate::{
+    Action, Attempt, Capabilities, Capability, Outcome, PowerBackend, ShutdownError,
+    ShutdownResult, Step, registry,
+};
+
+impl Bus {
+    async fn connect_async(self) -> zbus::Result<Connection> {
+        match self {
+            Bus::Session => Connection::session().await,
+            Bus::System => Connection::system().await,
+        }
+    }
+}
+
+async fn name_has_owner(conn: &Connection, name: &str) -> bool {
+    let reply = conn
+        .call_method(
+            Some("org.freedesktop.DBus"),
+            "/",
+            Some("org.freedesktop.DBus"),
+            "NameHasOwner",
+            &(name),
+        )
+        .await;
+    reply.and_then(|r| r.body().deserialize()).unwrap_or(false)
+}
+
+async fn dbus_send<B: Serialize + DynamicType + Sync>(
+    attempts: &mut Vec<Attempt>,
+    bus: Bus,
+    destination: &str,
+    path: &str,
+    interface: &str,
+    method: &str,
+    body: &B,
+) -> bool {
+    dbus_call(attempts, bus, destination, path, interface, method, body)
+        .await
+        .is_some()
+}
+
+/// Async version of [`super::dbus_call`].
+async fn dbus_call<B: Serialize + DynamicType + Sync>(
+    attempts: &mut Vec<Attempt>,
+    bus: Bus,
+    destination: &str,
+    path: &str,
+    interface: &str,
+    method: &str,
+    body: &B,
+) -> Option<Message> {
+    let backend = format!("{interface}.{method}");
+    let conn = match bus.connect_async().await {
+        Ok(conn) => conn,
+        Err(error) => {
+            attempts.push(Attempt::new(
+                backend,
+                error.to_string(),
+                ErrorKind::NotFound,
+            ));
+            return None;
+        }
+    };
+    if !name_has_owner(&conn, destination).await {
+        attempts.push(Attempt::new(
+            backend,
+            "org.freedesktop.DBus.Error.NameHasNoOwner",
+            ErrorKind::NotFound,
+        ));
+        return None;
+    }
+    let reply = conn
+        .call_method(Some(destination), path, Some(interface), method, body)
+        .await;
+    match reply {
+        Ok(reply) => Some(reply),
+        Err(error) => {
+            check_reply::<()>(attempts, backend, Err(error));
+            None
+        }
+    }
+}
+
+async fn dbus_query<B: Serialize + DynamicType + Sync, R: DeserializeOwned + Type>(
+    bus: Bus,
+    destination: &str,
+    path: &str,
+    interface: &str,
+    method: &str,
+    body: &B,
+) -> Option<R> {
+    let conn = bus.connect_async().await.ok()?;
+    if !name_has_owner(&conn, destination).await {
+        return None;
+    }
+    let reply = conn
+        .call_method(Some(destination), path, Some(interface), method, body)
+        .await
+        .ok()?;
+    reply.body().deserialize().ok()
+}
+
+async fn run_command(command: &str, args: &[&str]) -> ShutdownResult {
+    command_result(
+        command,
+        args,
+        Command::new(command).args(args).output().await,
+    )
+}
+
+impl DBusBackend {
+    async fn send_async<B: Serialize + DynamicType + Sync>(
+        &self,
+        attempts: &mut Vec<Attempt>,
+        method: &str,
+        body: &B,
+    ) -> bool {
+        let (bus, dest, path, iface) = (self.bus, self.destination, self.path, self.interface);
+        dbus_send(attempts, bus, dest, path, iface, method, body).await
+    }
+
+    async fn request_async<B, R>(
+        &self,
+        attempts: &mut Vec<Attempt>,
+        method: &str,
+        body: &B,
+    ) -> Option<R>
+    where
+        B: Serialize + DynamicType + Sync,
+        R: DeserializeOwned + Type,
+    {
+        let (bus, dest, path, iface) = (self.bus, self.destination, self.path, self.interface);
+        let reply = dbus_call(attempts, bus, dest, path, iface, method, body).await?;
+        self.deserialize(attempts, method, &reply)
+    }
+
+    async fn property_async<R>(&self, name: &str) -> Option<R>
+    where
+        R: TryFrom<OwnedValue>,
+        R::Error: Into<zbus::Error>,
+    {
+        let conn = self.bus.connect_async().await.ok()?;
+        if !name_has_owner(&conn, self.destination).await {
+            return None;
+        }
+        let proxy = Proxy::new(&conn, self.destination, self.path, self.interface)
+            .await
+            .ok()?;
+        proxy.get_property(name).await.ok()
+    }
+
+    async fn call_async(&self, attempts: &mut Vec<Attempt>, method: &str, args: Args) -> bool {
+        let Some(body) = self.body(attempts, method, args) else {
+            return false;
+        };
+        self.send_async(attempts, method, &body).await
+    }
+
+    pub(super) async fn probe_nonblocking(&self) -> bool {
+        match self.bus.connect_async().await {
+            Ok(conn) => name_has_owner(&conn, self.destination).await,
+            Err(_) => false,
+        }
+    }
+
+    pub(super) async fn perform_nonblocking(&self, action: Action) -> ShutdownResult {
+        let mut attempts = Vec::new();
+        for method in self.methods_for(action) {
+            let sent = self
+                .call_async(&mut attempts, method.name, method.args)
+                .await;
+            if let Some(result) = self.settle(method, sent, &mut attempts) {
+                return result;
+            }
+        }
+        Err(ShutdownError::from_attempts(attempts))
+    }
+}
+
+impl CommandBackend {
+    pub(super) async fn perform_nonblocking(&self, action: Action) -> ShutdownResult {
+        let mut attempts = Vec::new();
+        for line in CommandBackend::command_lines(action)? {
+            let args = line[1..].iter().map(String::as_str).collect::<Vec<_>>();
+            match run_command(&line[0], &args).await {
+                Ok(outcome) => return Ok(outcome),
+                Err(error) => attempts.extend_from_slice(error.attempts()),
+            }
+        }
+        Err(ShutdownError::from_attempts(attempts))
+    }
+}
+
+/// Async version of [`super::login1_or_command`].
+async fn login1_or_command<B: Serialize + DynamicType + Sync>(
+    method: &str,
+    body: &B,
+    command: &[&str],
+) -> ShutdownResult {
+    let mut attempts = Vec::new();
+    let sent = LOGIN1.send_async(&mut attempts, method, body).await;
+    match login1_result(method, sent, &mut attempts) {
+        Some(result) => result,
+        None => after_attempts(attempts, run_command(command[0], &command[1..]).await),
+    }
+}
+
+async fn perform_each(
+    attempts: &mut Vec<Attempt>,
+    backends: &[DBusBackend],
+    action: Action,
+) -> Option<Outcome> {
+    for backend in backends {
+        match backend.perform_nonblocking(action).await {
+            Ok(outcome) => return Some(outcome),
+            Err(error) => attempts.extend_from_slice(error.attempts()),
+        }
+    }
+    None
+}
+
+impl CapabilityQuery {
+    async fn query_async(&self) -> Option<Capability> {
+        let s = self.service;
+        if self.textual {
+            dbus_query::<_, String>(s.bus, s.destination, s.path, s.interface, self.method, &())
+                .await
+                .map(|reply| parse_capability(&reply))
+        } else {
+            dbus_query::<_, bool>(s.bus, s.destination, s.path, s.interface, self.method, &())
+                .await
+                .map(Capability::from)
+        }
+    }
+}
+
+/// Async version of the answer to the first of `queries` that gets a reply.
+async fn query_first(queries: &[CapabilityQuery]) -> Capability {
+    for query in queries {
+        if let Some(capability) = query.query_async().await {
+            return capability;
+        }
+    }
+    Capability::Unknown
+}
+
+/// Async version of [`crate::plan`].
+pub async fn plan(action: Action) -> Vec<Step> {
+    registry().plan_async(action).await
+}
+
+/// Async version of [`crate::plan_force`].
+pub async fn plan_force(action: Action) -> Vec<Step> {
+    let (backends, last_resort) = force_backends(action);
+    let mut steps = Vec::new();
+    for backend in backends.iter().filter(|backend| backend.supports(action)) {
+        steps.push(Step {
+            backend: backend.name().to_string(),
+            available: backend.probe_nonblocking().await,
+            invocations: backend.invocations(action),
+        });
+    }
+    steps.extend(last_resort);
+    steps
+}
+
+/// Async version of [`crate::perform_with`].
+pub async fn perform_with(action: Action, options: ActionOptions) -> ShutdownResult {
+    if let Some(target) = inhibit_target(action).filter(|_| options.fail_if_inhibited) {
+        check_inhibitors(target, list_inhibitors().await)?;
+    }
+    if options.lock_before_sleep && is_sleep(action) {
+        lock_screen().await?;
+    }
+    registry().perform_async(action).await
+}
+
+/// Async version of [`crate::list_inhibitors`].
+pub async fn list_inhibitors() -> ShutdownResult<Vec<InhibitorLock>> {
+    let mut attempts = Vec::new();
+    let reply = LOGIN1
+        .request_async::<_, Vec<InhibitorEntry>>(&mut attempts, "ListInhibitors", &())
+        .await;
+    let Some(locks) = reply else {
+        return Err(ShutdownError::from_attempts(attempts));
+    };
+    Ok(locks.into_iter().map(InhibitorLock::new).collect())
+}
+
+/// Async version of [`crate::capabilities`].
+pub async fn capabilities() -> Capabilities {
+    let queries = &CAPABILITY_QUERIES;
+    let reboot = query_first(queries.reboot).await;
+    Capabilities {
+        shutdown: query_first(queries.shutdown).await,
+        reboot,
+        suspend: query_first(queries.suspend).await,
+        hibernate: query_first(queries.hibernate).await,
+        hybrid_sleep: query_first(queries.hybrid_sleep).await,
+        suspend_then_hibernate: query_first(queries.suspend_then_hibernate).await,
+        kexec_reboot: kexec_capability(reboot),
+    }
+}
+
+/// Async version of [`crate::can_shutdown`].
+pub async fn can_shutdown() -> Capability {
+    query_first(CAPABILITY_QUERIES.shutdown).await
+}
+
+/// Async version of [`crate::can_reboot`].
+pub async fn can_reboot() -> Capability {
+    query_first(CAPABILITY_QUERIES.reboot).await
+}
+
+/// Async version of [`crate::can_suspend`].
+pub async fn can_suspend() -> Capability {
+    query_first(CAPABILITY_QUERIES.suspend).await
+}
+
+/// Async version of [`crate::can_hibernate`].
+pub async fn can_hibernate() -> Capability {
+    query_first(CAPABILITY_QUERIES.hibernate).await
+}
+
+/// Async version of [`crate::shutdown`].
+pub async fn shutdown() -> ShutdownResult {
+    registry().perform_async(Action::Shutdown).await
+}
+
+/// Async version of [`crate::force_shutdown`]. On the last resort path, `sync(2)` blocks until
+/// the filesystems are flushed.
+pub async fn force_shutdown() -> ShutdownResult {
+    let mut attempts = Vec::new();
+    let backends = &FORCE_SHUTDOWN_BACKENDS;
+    if let Some(outcome) = perform_each(&mut attempts, backends, Action::Shutdown).await {
+        return Ok(outcome);
+    }
+    // SAFETY: sync(2) has no memory safety requirements.
+    unsafe { libc::sync() };
+    after_attempts(attempts, SysRq::new().perform(b'o'))
+}
+
+/// Async version of [`crate::reboot`].
+pub async fn reboot() -> ShutdownResult {
+    registry().perform_async(Action::Reboot).await
+}
+
+/// Async version of [`crate::force_reboot`]. Writing to `/proc/sysrq-trigger` does not block.
+pub async fn force_reboot() -> ShutdownResult {
+    super::force_reboot()
+}
+
+/// Async version of [`crate::logout`].
+pub async fn logout() -> ShutdownResult {
+    registry().perform_async(Action::Logout).await
+}
+
+/// Async version of [`crate::force_logout`].
+pub async fn force_logout() -> ShutdownResult {
+    let mut attempts = Vec::new();
+    let backends = &FORCE_LOGOUT_BACKENDS;
+    if let Some(outcome) = perform_each(&mut attempts, backends, Action::Logout).await {
+        return Ok(outcome);
+    }
+    after_attempts(attempts, kill_session())
+}
+
+/// Async version of [`crate::sleep`].
+pub async fn sleep() -> ShutdownResult {
+    registry().perform_async(Action::Sleep).await
+}
+
+/// Async version of [`crate::hibernate`].
+pub async fn hibernate() -> ShutdownResult {
+    registry().perform_async(Action::Hibernate).await
+}
+
+/// Async version of [`crate::hybrid_sleep`].
+pub async fn hybrid_sleep() -> ShutdownResult {
+    registry().perform_async(Action::HybridSleep).await
+}
+
+/// Async version of [`crate::suspend_then_hibernate`].
+pub async fn suspend_then_hibernate() -> ShutdownResult {
+    registry().perform_async(Action::SuspendThenHibernate).await
+}
+
+/// Async version of [`crate::lock_screen`].
+pub async fn lock_screen() -> ShutdownResult {
+    registry().perform_async(Action::LockScreen).await
+}
+
+/// Async version of [`crate::halt`].
+pub async fn halt() -> ShutdownResult {
+    registry().perform_async(Action::Halt).await
+}
+
+/// Async version of [`crate::kexec_reboot`].
+pub async fn kexec_reboot() -> ShutdownResult {
+    registry().perform_async(Action::KexecReboot).await
+}
+
+/// Async version of [`crate::soft_reboot`].
+pub async fn soft_reboot() -> ShutdownResult {
+    registry().perform_async(Action::SoftReboot).await
+}
+
+/// Async version of [`super::schedule::set_wall_message`].
+async fn set_wall_message(attempts: &mut Vec<Attempt>, message: &str) -> bool {
+    let message_property = LOGIN1.property_async::<String>("WallMessage").await;
+    let enabled_property = LOGIN1.property_async::<bool>("EnableWallMessages").await;
+    let current = message_property.zip(enabled_property);
+    let set = LOGIN1
+        .send_async(attempts, "SetWallMessage", &(message, true))
+        .await;
+    if set {
+        schedule::save_wall_message(current);
+    }
+    set
+}
+
+/// Async version of [`super::schedule::restore_wall_message`].
+async fn restore_wall_message(attempts: &mut Vec<Attempt>) {
+    if let Some(saved) = schedule::take_saved_wall_message() {
+        LOGIN1.send_async(attempts, "SetWallMessage", &saved).await;
+    }
+}
+
+async fn schedule(
+    kind: &str,
+    flag: &str,
+    time: SystemTime,
+    message: Option<&str>,
+) -> ShutdownResult {
+    let method = "ScheduleShutdown";
+    let mut attempts = Vec::new();
+    // Without the message, the shut down is scheduled by the command, which warns the users
+    let message_set = match message {
+        Some(message) => set_wall_message(&mut attempts, message).await,
+        None => true,
+    };
+    let body = (kind, schedule::usec(time));
+    let sent = message_set && LOGIN1.send_async(&mut attempts, method, &body).await;
+    if !sent {
+        restore_wall_message(&mut attempts).await;
+    }
+    if let Some(result) = login1_result(method, sent, &mut attempts) {
+        return result;
+    }
+    let command = schedule::shutdown_command(flag, time, message);
+    let args = command[1..].iter().map(String::as_str).collect::<Vec<_>>();
+    after_attempts(attempts, run_command(&command[0], &args).await)
+}
+
+/// Async version of [`crate::shutdown_at`].
+pub async fn shutdown_at(time: SystemTime) -> ShutdownResult {
+    schedule("poweroff", "-h", time, None).await
+}
+
+/// Async version of [`crate::shutdown_in`].
+pub async fn shutdown_in(delay: Duration) -> ShutdownResult {
+    shutdown_at(schedule::time_in(delay)?).await
+}
+
+/// Async version of [`crate::reboot_at`].
+pub async fn reboot_at(time: SystemTime) -> ShutdownResult {
+    schedule("reboot", "-r", time, None).await
+}
+
+/// Async version of [`crate::reboot_in`].
+pub async fn reboot_in(delay: Duration) -> ShutdownResult {
+    reboot_at(schedule::time_in(delay)?).await
+}
+
+/// Async version of [`crate::shutdown_with_message`].
+pub async fn shutdown_with_message(
+    message: &str,
+    timeout: u32,
+    _force_close_apps: bool,
+) -> ShutdownResult {
+    let time = schedule::time_in(Duration::from_secs(timeout.into()))?;
+    schedule("poweroff", "-h", time, Some(message)).await
+}
+
+/// Async version of [`crate::reboot_with_message`].
+pub async fn reboot_with_message(
+    message: &str,
+    timeout: u32,
+    _force_close_apps: bool,
+) -> ShutdownResult {
+    let time = schedule::time_in(Duration::from_secs(timeout.into()))?;
+    schedule("reboot", "-r", time, Some(message)).await
+}
+
+/// Async version of [`crate::cancel_scheduled`].
+pub async fn cancel_scheduled() -> ShutdownResult {
+    let mut attempts = Vec::new();
+    let reply = LOGIN1
+        .request_async(&mut attempts, "CancelScheduledShutdown", &())
+        .await;
+    let outcome = match schedule::cancel_result(reply, &mut attempts) {
+        Some(result) => result,
+        None => after_attempts(attempts, run_command("shutdown", &["-c"]).await),
+    }?;
+    // The shut down is cancelled even if the original message cannot be restored
+    restore_wall_message(&mut Vec::new()).await;
+    Ok(outcome)
+}
+
+/// Async version of [`crate::plan_scheduled`].
+pub async fn plan_scheduled(action: Action, time: SystemTime, message: Option<&str>) -> Vec<Step> {
+    let Some((_, flag)) = schedule::schedule_kind(action) else {
+        return Vec::new();
+    };
+    let available = LOGIN1.probe_nonblocking().await;
+    schedule::scheduled_steps(flag, time, message, available)
+}
+
+/// Async version of [`crate::scheduled`].
+pub async fn scheduled() -> Option<ScheduledShutdown> {
+    LOGIN1
+        .property_async("ScheduledShutdown")
+        .await
+        .and_then(ScheduledShutdown::from_property)
+}
+
+/// Async version of [`super::boot::reboot_with`].
+async fn reboot_with<B: Serialize + DynamicType + Sync>(target: RebootTarget<B>) -> ShutdownResult {
+    let mut attempts = Vec::new();
+    let capability = CapabilityQuery::text(&LOGIN1, target.can)
+        .query_async()
+        .await;
+    if target.check(&mut attempts, capability)
+        && LOGIN1
+            .send_async(&mut attempts, target.method, &target.body)
+            .await
+    {
+        match reboot().await {
+            Ok(outcome) => return Ok(outcome),
+            Err(error) => {
+                attempts.extend_from_slice(error.attempts());
+                LOGIN1
+                    .send_async(&mut attempts, target.method, &target.reset)
+                    .await;
+                if matches!(error, ShutdownError::Cancelled(_)) {
+                    return Err(ShutdownError::from_attempts(attempts));
+                }
+            }
+        }
+    }
+    let args = target.command[1..]
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    after_attempts(attempts, run_command(&target.command[0], &args).await)
+}
+
+/// Async version of [`crate::reboot_to_firmware_setup`].
+pub async fn reboot_to_firmware_setup() -> ShutdownResult {
+    reboot_with(RebootTarget::firmware_setup()).await
+}
+
+/// Async version of [`crate::list_boot_entries`].
+pub async fn list_boot_entries() -> ShutdownResult<Vec<String>> {
+    LOGIN1
+        .property_async("BootLoaderEntries")
+        .await
+        .ok_or_else(boot_entries_unavailable)
+}
+
+/// Async version of [`crate::reboot_to_boot_entry`].
+pub async fn reboot_to_boot_entry(id: &str) -> ShutdownResult {
+    reboot_with(RebootTarget::boot_entry(id)).await
+}
+
+/// Async version of [`crate::reboot_to_boot_loader_menu`].
+pub async fn reboot_to_boot_loader_menu(timeout: Duration) -> ShutdownResult {
+    reboot_with(RebootTarget::boot_loader_menu(timeout)).await
+}
+
+/// Async version of [`super::session::properties`].
+async fn properties(
+    conn: Option<&Connection>,
+    path: &OwnedObjectPath,
+    interface: &str,
+) -> HashMap<String, OwnedValue> {
+    let Some(conn) = conn else {
+        return HashMap::new();
+    };
+    let dest = LOGIN1.destination;
+    conn.call_method(
+        Some(dest),
+        path,
+        Some(PROPERTIES_INTERFACE),
+        "GetAll",
+        &(interface),
+    )
+    .await
+    .and_then(|reply| reply.body().deserialize())
+    .unwrap_or_default()
+}
+
+/// Async version of [`crate::list_sessions`].
+pub async fn list_sessions() -> ShutdownResult<Vec<LoginSession>> {
+    let mut attempts = Vec::new();
+    let reply = LOGIN1
+        .request_async::<_, Vec<SessionEntry>>(&mut attempts, "ListSessions", &())
+        .await;
+    let Some(sessions) = reply else {
+        return Err(ShutdownError::from_attempts(attempts));
+    };
+    let conn = Bus::System.connect_async().await.ok();
+    let mut list = Vec::with_capacity(sessions.len());
+    for session in sessions {
+        let properties = properties(conn.as_ref(), &session.4, SESSION_INTERFACE).await;
+        list.push(LoginSession::new(session, &properties));
+    }
+    Ok(list)
+}
+
+/// Async version of [`crate::list_users`].
+pub async fn list_users() -> ShutdownResult<Vec<LoginUser>> {
+    let mut attempts = Vec::new();
+    let reply = LOGIN1
+        .request_async::<_, Vec<UserEntry>>(&mut attempts, "ListUsers", &())
+        .await;
+    let Some(users) = reply else {
+        return Err(ShutdownError::from_attempts(attempts));
+    };
+    let conn = Bus::System.connect_async().await.ok();
+    let mut list = Vec::with_capacity(users.len());
+    for user in users {
+        let properties = properties(conn.as_ref(), &user.2, USER_INTERFACE).await;
+        list.push(LoginUser::new(user, &properties));
+    }
+    Ok(list)
+}
+
+/// Async version of [`crate::logout_session`].
+pub async fn logout_session(id: &str) -> ShutdownResult {
+    login1_or_command(
+        "TerminateSession",
+        &(id),
+        &["loginctl", "terminate-session", id],
+    )
+    .await
+}
+
+/// Async version of [`crate::logout_user`].
+pub async fn logout_user(uid: u32) -> ShutdownResult {
+    login1_or_command(
+        "TerminateUser",
+        &(uid),
+        &["loginctl", "terminate-user", &uid.to_string()],
+    )
+    .await
+}
@@ -0,0 +1,219 @@
+//! Reboots into something else than the default boot entry.
+
+use std::io::ErrorKind;
+use std::time::Duration;
+
+use zbus::export::serde::Serialize;
+use zbus::zvariant::DynamicType;
+
+use super::{CapabilityQuery, LOGIN1, after_attempts, reboot, run_command};
+use crate::{Attempt, Capability, ShutdownError, ShutdownResult};
+
+/// A setting of logind applying to the next reboot only, and the command doing the same reboot.
+pub(super) struct RebootTarget<B> {
+    /// The method telling whether the setting can be applied.
+    pub(super) can: &'static str,
+    pub(super) method: &'static str,
+    pub(super) body: B,
+    /// The body of the call undoing the setting.
+    pub(super) reset: B,
+    pub(super) command: Vec<String>,
+}
+
+fn systemctl_reboot(option: String) -> Vec<String> {
+    vec!["systemctl".to_string(), "reboot".to_string(), option]
+}
+
+impl RebootTarget<bool> {
+    pub(super) fn firmware_setup() -> Self {
+        RebootTarget {
+            can: "CanRebootToFirmwareSetup",
+            method: "SetRebootToFirmwareSetup",
+            body: true,
+            reset: false,
+            command: systemctl_reboot("--firmware-setup".to_string()),
+        }
+    }
+}
+
+impl<'a> RebootTarget<&'a str> {
+    pub(super) fn boot_entry(id: &'a str) -> Self {
+        RebootTarget {
+            can: "CanRebootToBootLoaderEntry",
+            method: "SetRebootToBootLoaderEntry",
+            body: id,
+            reset: "",
+            command: systemctl_reboot(format!("--boot-loader-entry={id}")),
+        }
+    }
+}
+
+impl RebootTarget<u64> {
+    pub(super) fn boot_loader_menu(timeout: Duration) -> Self {
+        // Whole seconds, rounded up, so that logind and systemctl get the same timeout. u64::MAX
+        // microseconds would disable the menu.
+        let seconds = timeout
+            .as_secs()
+            .saturating_add(u64::from(timeout.subsec_nanos() > 0))
+            .min((u64::MAX - 1) / 1_000_000);
+        RebootTarget {
+            can: "CanRebootToBootLoaderMenu",
+            method: "SetRebootToBootLoaderMenu",
+            body: seconds * 1_000_000,
+            // Disables the menu
+            reset: u64::MAX,
+            command: systemctl_reboot(format!("--boot-loader-menu={seconds}s")),
+        }
+    }
+}
+
+impl<B> RebootTarget<B> {
+    /// Whether `capability`, the reply to the `can` method, allows applying the setting,
+    /// recording why not.
+    pub(super) fn check(
+        &self,
+        attempts: &mut Vec<Attempt>,
+        capability: Option<Capability>,
+    ) -> bool {
+        let backend = format!("{}.{}", LOGIN1.interface, self.can);
+        match capability {
+            Some(capability) if capability.is_possible() => return true,
+            Some(Capability::No) => {
+                attempts.push(Attempt::new(backend, "no", ErrorKind::PermissionDenied));
+            }
+            Some(Capability::NotApplicable) => {
+                attempts.push(Attempt::new(backend, "na", ErrorKind::Unsupported));
+            }
+            _ => {}
+        }
+        false
+    }
+}
+
+/// Applies the setting of `target` if logind reports it is possible, then reboots the machine.
+/// Runs the command of `target` as a last resort. The setting is undone if the reboot fails, so
+/// that it does not apply to the next reboot.
+fn reboot_with<B: Serialize + DynamicType>(target: RebootTarget<B>) -> ShutdownResult {
+    let mut attempts = Vec::new();
+    let capability = CapabilityQuery::text(&LOGIN1, target.can).query();
+    if target.check(&mut attempts, capability)
+        && LOGIN1.send(&mut attempts, target.method, &target.body)
+    {
+        match reboot() {
+            Ok(outcome) => return Ok(outcome),
+            Err(error) => {
+                attempts.extend_from_slice(error.attempts());
+                LOGIN1.send(&mut attempts, target.method, &target.reset);
+                if matches!(error, ShutdownError::Cancelled(_)) {
+                    return Err(ShutdownError::from_attempts(attempts));
+                }
+            }
+        }
+    }
+    let args = target.command[1..]
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    after_attempts(attempts, run_command(&target.command[0], &args))
+}
+
+/// The error returned when the boot loader entries cannot be read.
+pub(super) fn boot_entries_unavailable() -> ShutdownError {
+    let backend = format!("{}.BootLoaderEntries", LOGIN1.interface);
+    let reason = "could not read the boot loader entries";
+    ShutdownError::from_attempts(vec![Attempt::new(backend, reason, ErrorKind::NotFound)])
+}
+
+/// Linux specific function to reboot the machine into the firmware setup (UEFI or BIOS).
+/// The following D-BUS calls are attempted:
+/// - org.freedesktop.login1.Manager.CanRebootToFirmwareSetup()
+/// - org.freedesktop.login1.Manager.SetRebootToFirmwareSetup(true), then the calls listed in
+///   [`crate::reboot`]
+///
+/// If nothing works up to this point, as a last resort this function calls
+/// `systemctl reboot --firmware-setup`.
+pub fn reboot_to_firmware_setup() -> ShutdownResult {
+    reboot_with(RebootTarget::firmware_setup())
+}
+
+/// Linux specific function listing the IDs of the boot loader entries, read from the
+/// `BootLoaderEntries` property of org.freedesktop.login1.Manager.
+pub fn list_boot_entries() -> ShutdownResult<Vec<String>> {
+    LOGIN1
+        .property("BootLoaderEntries")
+        .ok_or_else(boot_entries_unavailable)
+}
+
+/// Linux specific function to reboot the machine into the boot loader entry `id`, once.
+/// The following D-BUS calls are attempted:
+/// - org.freedesktop.login1.Manager.CanRebootToBootLoaderEntry()
+/// - org.freedesktop.login1.Manager.SetRebootToBootLoaderEntry(id), then the calls listed in
+///   [`crate::reboot`]
+///
+/// If nothing works up to this point, as a last resort this function calls
+/// `systemctl reboot --boot-loader-entry=id`. See [`list_boot_entries`] for the available IDs.
+pub fn reboot_to_boot_entry(id: &str) -> ShutdownResult {
+    reboot_with(RebootTarget::boot_entry(id))
+}
+
+/// Linux specific function to reboot the machine showing the boot loader menu once, for `timeout`.
+/// The following D-BUS calls are attempted:
+/// - org.freedesktop.login1.Manager.CanRebootToBootLoaderMenu()
+/// - org.freedesktop.login1.Manager.SetRebootToBootLoaderMenu(timeout), then the calls listed in
+///   [`crate::reboot`]
+///
+/// If nothing works up to this point, as a last resort this function calls
+/// `systemctl reboot --boot-loader-menu=timeout`. Both get `timeout` rounded up to whole seconds;
+/// a zero `timeout` shows the menu until an entry is chosen.
+pub fn reboot_to_boot_loader_menu(timeout: Duration) -> ShutdownResult {
+    reboot_with(RebootTarget::boot_loader_menu(timeout))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_records_why_the_setting_cannot_be_applied() {
+        let target = RebootTarget::firmware_setup();
+        let cases = [
+            (Some(Capability::Yes), true, None),
+            (Some(Capability::Challenge), true, None),
+            (
+                Some(Capability::No),
+                false,
+                Some(ErrorKind::PermissionDenied),
+            ),
+            (
+                Some(Capability::NotApplicable),
+                false,
+                Some(ErrorKind::Unsupported),
+            ),
+            (Some(Capability::Unknown), false, None),
+            (None, false, None),
+        ];
+        for (capability, possible, kind) in cases {
+            let mut attempts = Vec::new();
+            assert_eq!(target.check(&mut attempts, capability), possible);
+            assert_eq!(attempts.first().map(|attempt| attempt.kind), kind);
+        }
+    }
+
+    #[test]
+    fn boot_loader_menu_gives_logind_and_systemctl_the_same_timeout() {
+        let cases = [
+            (Duration::ZERO, 0),
+            (Duration::from_micros(1), 1),
+            (Duration::from_millis(1500), 2),
+            (Duration::from_secs(30), 30),
+            // never u64::MAX microseconds, which disables the menu
+            (Duration::MAX, 18_446_744_073_709),
+        ];
+        for (timeout, seconds) in cases {
+            let target = RebootTarget::boot_loader_menu(timeout);
+            assert_eq!(target.body, seconds * 1_000_000, "{timeout:?}");
+            let option = format!("--boot-loader-menu={seconds}s");
+            assert_eq!(target.command, ["systemctl", "reboot", &option]);
+        }
+    }
+}
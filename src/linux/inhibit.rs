@@ -0,0 +1,163 @@
+//! Inhibitor locks, holding back power actions while they are taken.
+//!
+//! Reference: <https://systemd.io/INHIBITOR_LOCKS/>
+
+use std::os::fd::{AsFd, BorrowedFd, OwnedFd};
+
+use zbus::zvariant;
+
+use super::LOGIN1;
+use crate::{ShutdownError, ShutdownResult};
+
+/// A power action an [`Inhibitor`] holds back.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum InhibitTarget {
+    /// Shutting down and rebooting the machine.
+    Shutdown,
+    /// Putting the machine to sleep and hibernating it.
+    Sleep,
+    /// Detecting that the machine is idle, and the actions taken when it is.
+    Idle,
+    /// Handling the power key by logind.
+    HandlePowerKey,
+}
+
+impl InhibitTarget {
+    fn as_str(self) -> &'static str {
+        match self {
+            InhibitTarget::Shutdown => "shutdown",
+            InhibitTarget::Sleep => "sleep",
+            InhibitTarget::Idle => "idle",
+            InhibitTarget::HandlePowerKey => "handle-power-key",
+        }
+    }
+}
+
+/// How an [`Inhibitor`] holds back the power actions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum InhibitMode {
+    /// The actions are refused for as long as the lock is taken.
+    Block,
+    /// The actions are delayed until the lock is released, or for a limited time at most.
+    Delay,
+}
+
+impl InhibitMode {
+    fn as_str(self) -> &'static str {
+        match self {
+            InhibitMode::Block => "block",
+            InhibitMode::Delay => "delay",
+        }
+    }
+}
+
+/// An inhibitor lock taken with logind, released when dropped.
+///
+/// ```no_run
+/// use system_shutdown::{InhibitMode, InhibitTarget, Inhibitor};
+///
+/// let targets = [InhibitTarget::Shutdown, InhibitTarget::Sleep];
+/// match Inhibitor::new(&targets, "backup", "Backup in progress", InhibitMode::Block) {
+///     Ok(_inhibitor) => println!("Running the backup job"),
+///     Err(error) => eprintln!("Failed to take the lock: {}", error),
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Inhibitor {
+    fd: OwnedFd,
+}
+
+impl Inhibitor {
+    /// Takes a lock holding back `targets` in the given `mode`, on behalf of the application
+    /// `who`, for the human readable reason `why`.
+    /// The following D-BUS call is attempted:
+    /// - org.freedesktop.login1.Manager.Inhibit(what, who, why, mode)
+    pub fn new(
+        targets: &[InhibitTarget],
+        who: &str,
+        why: &str,
+        mode: InhibitMode,
+    ) -> ShutdownResult<Inhibitor> {
+        let what = targets
+            .iter()
+            .map(|target| target.as_str())
+            .collect::<Vec<_>>()
+            .join(":");
+        let mut attempts = Vec::new();
+        let body = (what, who, why, mode.as_str());
+        match LOGIN1.request::<_, zvariant::OwnedFd>(&mut attempts, "Inhibit", &body) {
+            Some(fd) => Ok(Inhibitor { fd: fd.into() }),
+            None => Err(ShutdownError::from_attempts(attempts)),
+        }
+    }
+
+    /// Releases the lock. Same as dropping the inhibitor.
+    pub fn release(self) {}
+}
+
+impl AsFd for Inhibitor {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+/// An inhibitor lock taken by an application, as returned by [`list_inhibitors`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct InhibitorLock {
+    /// The actions held back, separated by colons, e.g. `"shutdown:sleep"`.
+    pub what: String,
+    /// The application that took the lock.
+    pub who: String,
+    /// Why the lock was taken.
+    pub why: String,
+    /// `"block"` or `"delay"`.
+    pub mode: String,
+    /// The user ID of the process that took the lock.
+    pub uid: u32,
+    /// The ID of the process that took the lock.
+    pub pid: u32,
+}
+
+impl InhibitorLock {
+    /// Checks whether the lock holds back `target`.
+    pub fn holds(&self, target: InhibitTarget) -> bool {
+        self.what.split(':').any(|what| what == target.as_str())
+    }
+
+    /// Checks whether the lock was taken in `mode`.
+    pub fn is(&self, mode: InhibitMode) -> bool {
+        self.mode == mode.as_str()
+    }
+}
+
+/// An entry of the reply to org.freedesktop.login1.Manager.ListInhibitors().
+pub(super) type InhibitorEntry = (String, String, String, String, u32, u32);
+
+impl InhibitorLock {
+    /// Builds the lock from its `ListInhibitors` entry.
+    pub(super) fn new((what, who, why, mode, uid, pid): InhibitorEntry) -> Self {
+        InhibitorLock {
+            what,
+            who,
+            why,
+            mode,
+            uid,
+            pid,
+        }
+    }
+}
+
+/// Linux specific function listing the inhibitor locks currently taken.
+/// The following D-BUS call is attempted:
+/// - org.freedesktop.login1.Manager.ListInhibitors()
+pub fn list_inhibitors() -> ShutdownResult<Vec<InhibitorLock>> {
+    let mut attempts = Vec::new();
+    let reply = LOGIN1.request::<_, Vec<InhibitorEntry>>(&mut attempts, "ListInhibitors", &());
+    let Some(locks) = reply else {
+        return Err(ShutdownError::from_attempts(attempts));
+    };
+    Ok(locks.into_iter().map(InhibitorLock::new).collect())
+}
@@ -0,0 +1,121 @@
+//! Notifications sent by logind before and after the machine shuts down or sleeps.
+
+use std::io::ErrorKind;
+
+use zbus::MatchRule;
+use zbus::blocking::MessageIterator;
+use zbus::message::Type as MessageType;
+
+use super::{Bus, LOGIN1};
+use crate::{Attempt, InhibitMode, InhibitTarget, Inhibitor, ShutdownError, ShutdownResult};
+
+/// A notification sent by logind, as returned by [`PowerEvents`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum PowerEvent {
+    /// The machine is about to shut down or reboot if `true`. `false` means the shut down was
+    /// cancelled.
+    PrepareForShutdown(bool),
+    /// The machine is about to sleep or hibernate if `true`. `false` means it just resumed.
+    PrepareForSleep(bool),
+}
+
+/// An iterator over the `PrepareForShutdown` and `PrepareForSleep` signals of
+/// org.freedesktop.login1.Manager, blocking until the next one is received.
+///
+/// Created with [`PowerEvents::with_delay`], it holds a delay inhibitor lock so that logind waits
+/// for each event to be handled: the lock is released when the next event is requested, and taken
+/// again once the machine resumes.
+///
+/// ```no_run
+/// use system_shutdown::{PowerEvent, PowerEvents};
+///
+/// let events = PowerEvents::with_delay("editor", "Saving the open documents").unwrap();
+/// for event in events {
+///     if event == PowerEvent::PrepareForShutdown(true) {
+///         println!("Saving the open documents");
+///     }
+/// }
+/// ```
+pub struct PowerEvents {
+    messages: MessageIterator,
+    /// Who takes the delay lock and why, if one is taken.
+    delay: Option<(String, String)>,
+    inhibitor: Option<Inhibitor>,
+    /// Whether the lock is to be released before waiting for the next event.
+    release: bool,
+}
+
+impl PowerEvents {
+    /// Subscribes to the signals, without holding back the power actions.
+    pub fn new() -> ShutdownResult<Self> {
+        let fail = |error: zbus::Error| {
+            let backend = format!("{}.PrepareForShutdown", LOGIN1.interface);
+            let attempt = Attempt::new(backend, error.to_string(), ErrorKind::NotFound);
+            ShutdownError::from_attempts(vec![attempt])
+        };
+        let rule = MatchRule::builder()
+            .msg_type(MessageType::Signal)
+            .sender(LOGIN1.destination)
+            .and_then(|rule| rule.path(LOGIN1.path))
+            .and_then(|rule| rule.interface(LOGIN1.interface))
+            .map_err(fail)?
+            .build();
+        let conn = Bus::System.connect().map_err(fail)?;
+        let messages = MessageIterator::for_match_rule(rule, &conn, None).map_err(fail)?;
+        Ok(PowerEvents {
+            messages,
+            delay: None,
+            inhibitor: None,
+            release: false,
+        })
+    }
+
+    /// Subscribes to the signals, delaying the shut downs and sleeps until each event is handled.
+    /// `who` and `why` describe the delay lock, see [`Inhibitor::new`].
+    pub fn with_delay(who: &str, why: &str) -> ShutdownResult<Self> {
+        let mut events = PowerEvents::new()?;
+        events.inhibitor = Some(delay_lock(who, why)?);
+        events.delay = Some((who.to_string(), why.to_string()));
+        Ok(events)
+    }
+}
+
+fn delay_lock(who: &str, why: &str) -> ShutdownResult<Inhibitor> {
+    let targets = [InhibitTarget::Shutdown, InhibitTarget::Sleep];
+    Inhibitor::new(&targets, who, why, InhibitMode::Delay)
+}
+
+impl Iterator for PowerEvents {
+    type Item = PowerEvent;
+
+    fn next(&mut self) -> Option<PowerEvent> {
+        if self.release {
+            // The previous event was handled, let the action proceed
+            self.inhibitor = None;
+            self.release = false;
+        }
+        loop {
+            let Ok(message) = self.messages.next()? else {
+                continue;
+            };
+            let header = message.header();
+            let Ok(start) = message.body().deserialize::<bool>() else {
+                continue;
+            };
+            let event = match header.member().map(|member| member.as_str()) {
+                Some("PrepareForShutdown") => PowerEvent::PrepareForShutdown(start),
+                Some("PrepareForSleep") => PowerEvent::PrepareForSleep(start),
+                _ => continue,
+            };
+            if let Some((who, why)) = &self.delay {
+                if start {
+                    self.release = true;
+                } else if self.inhibitor.is_none() {
+                    self.inhibitor = delay_lock(who, why).ok();
+                }
+            }
+            return Some(event);
+        }
+    }
+}
@@ -0,0 +1,351 @@
+//! Shut downs and reboots scheduled for later, managed by logind.
+
+use std::io::{self, ErrorKind};
+use std::mem;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::{LOGIN1, after_attempts, login1_result, run_command};
+use crate::{Action, Attempt, Invocation, PowerBackend, ShutdownError, ShutdownResult, Step};
+
+/// A shut down or reboot scheduled with logind, as returned by [`scheduled`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ScheduledShutdown {
+    /// What is scheduled: `"poweroff"`, `"reboot"` or `"halt"`. The `"dry-poweroff"`,
+    /// `"dry-reboot"` and `"dry-halt"` variants only warn the logged in users.
+    pub kind: String,
+    /// When it is scheduled.
+    pub time: SystemTime,
+}
+
+impl ScheduledShutdown {
+    /// Reads the `ScheduledShutdown` property of logind, empty if nothing is scheduled.
+    pub(super) fn from_property((kind, usec): (String, u64)) -> Option<Self> {
+        if kind.is_empty() {
+            return None;
+        }
+        Some(ScheduledShutdown {
+            kind,
+            time: UNIX_EPOCH + Duration::from_micros(usec),
+        })
+    }
+}
+
+/// The time once `delay` has elapsed, failing if it cannot be represented.
+pub(super) fn time_in(delay: Duration) -> ShutdownResult<SystemTime> {
+    SystemTime::now().checked_add(delay).ok_or_else(|| {
+        let error = io::Error::new(ErrorKind::InvalidInput, "the delay is too long");
+        ShutdownError::Io(error)
+    })
+}
+
+/// The time of a scheduled shut down, in microseconds since the epoch as logind expects it.
+pub(super) fn usec(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros() as u64
+}
+
+/// The `shutdown` command scheduling the shut down at `time`, the fallback if logind fails.
+pub(super) fn shutdown_command(flag: &str, time: SystemTime, message: Option<&str>) -> Vec<String> {
+    let minutes = time
+        .duration_since(SystemTime::now())
+        .unwrap_or_default()
+        .as_secs()
+        .div_ceil(60);
+    let when = match minutes {
+        0 => "now".to_string(),
+        minutes => format!("+{minutes}"),
+    };
+    let mut command = vec!["shutdown".to_string(), flag.to_string(), when];
+    command.extend(message.map(str::to_string));
+    command
+}
+
+/// The wall message of logind and whether wall messages were enabled before a message passed to
+/// [`shutdown_with_message`] or [`reboot_with_message`] replaced them. They are restored if the
+/// shut down cannot be scheduled, or once it is cancelled.
+static SAVED_WALL_MESSAGE: Mutex<Option<(String, bool)>> = Mutex::new(None);
+
+/// Saves the wall message `current` that a message is replacing, unless the original one is
+/// saved already.
+pub(super) fn save_wall_message(current: Option<(String, bool)>) {
+    let mut saved = SAVED_WALL_MESSAGE
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    if saved.is_none() {
+        *saved = current;
+    }
+}
+
+/// Takes the wall message to restore, if one was replaced.
+pub(super) fn take_saved_wall_message() -> Option<(String, bool)> {
+    SAVED_WALL_MESSAGE
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take()
+}
+
+/// Sets the wall message announcing the shut down, saving the current one. Returns whether it
+/// was set.
+fn set_wall_message(attempts: &mut Vec<Attempt>, message: &str) -> bool {
+    let current = LOGIN1
+        .property::<String>("WallMessage")
+        .zip(LOGIN1.property::<bool>("EnableWallMessages"));
+    let set = LOGIN1.send(attempts, "SetWallMessage", &(message, true));
+    if set {
+        save_wall_message(current);
+    }
+    set
+}
+
+/// Restores the wall message replaced by [`set_wall_message`], if any.
+fn restore_wall_message(attempts: &mut Vec<Attempt>) {
+    if let Some(saved) = take_saved_wall_message() {
+        LOGIN1.send(attempts, "SetWallMessage", &saved);
+    }
+}
+
+/// The kind of shut down logind schedules for `action`, and the matching `shutdown` flag.
+pub(super) fn schedule_kind(action: Action) -> Option<(&'static str, &'static str)> {
+    match action {
+        Action::Shutdown => Some(("poweroff", "-h")),
+        Action::Reboot => Some(("reboot", "-r")),
+        _ => None,
+    }
+}
+
+/// The steps scheduling a shut down at `time` with `message`, `available` telling whether logind
+/// is running.
+pub(super) fn scheduled_steps(
+    flag: &str,
+    time: SystemTime,
+    message: Option<&str>,
+    available: bool,
+) -> Vec<Step> {
+    let methods = message
+        .map(|_| "SetWallMessage")
+        .into_iter()
+        .chain(["ScheduleShutdown"]);
+    let command = shutdown_command(flag, time, message).join(" ");
+    vec![
+        Step {
+            backend: LOGIN1.destination.to_string(),
+            available,
+            invocations: methods.map(|method| LOGIN1.invocation(method)).collect(),
+        },
+        Step {
+            backend: "command".to_string(),
+            available: true,
+            invocations: vec![Invocation::Command(command)],
+        },
+    ]
+}
+
+/// Turns the reply of logind to `CancelScheduledShutdown` into the result of
+/// [`cancel_scheduled`], or `None` if logind could not be reached and `shutdown -c` is to be run.
+/// `shutdown -c` makes the same call on systemd and succeeds whatever the reply, so it is not run
+/// if logind replied that nothing was scheduled.
+pub(super) fn cancel_result(
+    reply: Option<bool>,
+    attempts: &mut Vec<Attempt>,
+) -> Option<ShutdownResult> {
+    let method = "CancelScheduledShutdown";
+    if reply == Some(false) {
+        let backend = format!("{}.{method}", LOGIN1.interface);
+        attempts.push(Attempt::new(
+            backend,
+            "no shut down is scheduled",
+            ErrorKind::Other,
+        ));
+        return Some(Err(ShutdownError::from_attempts(mem::take(attempts))));
+    }
+    login1_result(method, reply.is_some(), attempts)
+}
+
+fn schedule(kind: &str, flag: &str, time: SystemTime, message: Option<&str>) -> ShutdownResult {
+    let method = "ScheduleShutdown";
+    let mut attempts = Vec::new();
+    // Without the message, the shut down is scheduled by the command, which warns the users
+    let sent = message.is_none_or(|message| set_wall_message(&mut attempts, message))
+        && LOGIN1.send(&mut attempts, method, &(kind, usec(time)));
+    if !sent {
+        restore_wall_message(&mut attempts);
+    }
+    login1_result(method, sent, &mut attempts).unwrap_or_else(|| {
+        let command = shutdown_command(flag, time, message);
+        let args = command[1..].iter().map(String::as_str).collect::<Vec<_>>();
+        after_attempts(attempts, run_command(&command[0], &args))
+    })
+}
+
+/// Linux specific function to shut down the machine at `time`.
+/// The following D-BUS call is attempted:
+/// - org.freedesktop.login1.Manager.ScheduleShutdown("poweroff", time)
+///
+/// If it fails, as a last resort this function calls `shutdown -h +N`, where `N` is the number of
+/// minutes left until `time`, rounded up.
+pub fn shutdown_at(time: SystemTime) -> ShutdownResult {
+    schedule("poweroff", "-h", time, None)
+}
+
+/// Linux specific function to shut down the machine once `delay` has elapsed. See [`shutdown_at`].
+/// Fails with [`std::io::ErrorKind::InvalidInput`] if `delay` is too long to be represented.
+pub fn shutdown_in(delay: Duration) -> ShutdownResult {
+    shutdown_at(time_in(delay)?)
+}
+
+/// Linux specific function to reboot the machine at `time`.
+/// The following D-BUS call is attempted:
+/// - org.freedesktop.login1.Manager.ScheduleShutdown("reboot", time)
+///
+/// If it fails, as a last resort this function calls `shutdown -r +N`, where `N` is the number of
+/// minutes left until `time`, rounded up.
+pub fn reboot_at(time: SystemTime) -> ShutdownResult {
+    schedule("reboot", "-r", time, None)
+}
+
+/// Linux specific function to reboot the machine once `delay` has elapsed. See [`reboot_at`].
+/// Fails with [`std::io::ErrorKind::InvalidInput`] if `delay` is too long to be represented.
+pub fn reboot_in(delay: Duration) -> ShutdownResult {
+    reboot_at(time_in(delay)?)
+}
+
+/// Linux specific function to shut down the machine in `timeout` seconds, warning the logged in
+/// users with `message`.
+/// The following D-BUS calls are attempted:
+/// - org.freedesktop.login1.Manager.SetWallMessage(message, true)
+/// - org.freedesktop.login1.Manager.ScheduleShutdown("poweroff", now + timeout)
+///
+/// The replaced wall message is restored if the shut down cannot be scheduled, or once it is
+/// cancelled with [`cancel_scheduled`].
+/// If the message cannot be set or the shut down cannot be scheduled, as a last resort this function calls `shutdown -h +N message`, where `N` is
+/// `timeout` in minutes, rounded up.
+/// Applications are always closed when the machine shuts down, so `force_close_apps` has no effect.
+pub fn shutdown_with_message(
+    message: &str,
+    timeout: u32,
+    _force_close_apps: bool,
+) -> ShutdownResult {
+    let time = time_in(Duration::from_secs(timeout.into()))?;
+    schedule("poweroff", "-h", time, Some(message))
+}
+
+/// Linux specific function to reboot the machine in `timeout` seconds, warning the logged in
+/// users with `message`.
+/// The following D-BUS calls are attempted:
+/// - org.freedesktop.login1.Manager.SetWallMessage(message, true)
+/// - org.freedesktop.login1.Manager.ScheduleShutdown("reboot", now + timeout)
+///
+/// The replaced wall message is restored if the shut down cannot be scheduled, or once it is
+/// cancelled with [`cancel_scheduled`].
+/// If the message cannot be set or the shut down cannot be scheduled, as a last resort this function calls `shutdown -r +N message`, where `N` is
+/// `timeout` in minutes, rounded up.
+/// Applications are always closed when the machine reboots, so `force_close_apps` has no effect.
+pub fn reboot_with_message(message: &str, timeout: u32, _force_close_apps: bool) -> ShutdownResult {
+    let time = time_in(Duration::from_secs(timeout.into()))?;
+    schedule("reboot", "-r", time, Some(message))
+}
+
+/// Linux specific function to cancel the scheduled shut down or reboot.
+/// The following D-BUS call is attempted:
+/// - org.freedesktop.login1.Manager.CancelScheduledShutdown()
+///
+/// If logind cannot be reached, as a last resort this function calls `shutdown -c`. It fails if
+/// logind replies that nothing was scheduled.
+///
+/// The wall message replaced by [`crate::shutdown_with_message`] or
+/// [`crate::reboot_with_message`] is restored once the shut down is cancelled.
+pub fn cancel_scheduled() -> ShutdownResult {
+    let mut attempts = Vec::new();
+    let reply = LOGIN1.request(&mut attempts, "CancelScheduledShutdown", &());
+    let outcome = cancel_result(reply, &mut attempts)
+        .unwrap_or_else(|| after_attempts(attempts, run_command("shutdown", &["-c"])))?;
+    // The shut down is cancelled even if the original message cannot be restored
+    restore_wall_message(&mut Vec::new());
+    Ok(outcome)
+}
+
+/// Linux specific function listing the steps [`shutdown_at`] or [`reboot_at`] would take to
+/// schedule `action` at `time`, or those of [`crate::shutdown_with_message`] or
+/// [`crate::reboot_with_message`] if there is a `message`. Empty for the other actions.
+pub fn plan_scheduled(action: Action, time: SystemTime, message: Option<&str>) -> Vec<Step> {
+    let Some((_, flag)) = schedule_kind(action) else {
+        return Vec::new();
+    };
+    scheduled_steps(flag, time, message, LOGIN1.probe())
+}
+
+/// Linux specific function returning the scheduled shut down or reboot, read from the
+/// `ScheduledShutdown` property of org.freedesktop.login1.Manager.
+///
+/// Returns `None` if nothing is scheduled, or if logind is not available.
+pub fn scheduled() -> Option<ScheduledShutdown> {
+    LOGIN1
+        .property("ScheduledShutdown")
+        .and_then(ScheduledShutdown::from_property)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shutdown_command_rounds_the_delay_up_to_minutes() {
+        let now = SystemTime::now();
+        let cases = [(0, "now"), (1, "+1"), (60, "+1"), (61, "+2"), (3600, "+60")];
+        for (delay, when) in cases {
+            let time = now + Duration::from_secs(delay) + Duration::from_millis(500);
+            let command = shutdown_command("-h", time, None);
+            assert_eq!(command, ["shutdown", "-h", when], "delay {delay}s");
+        }
+        let past = shutdown_command("-r", now - Duration::from_secs(10), Some("bye"));
+        assert_eq!(past, ["shutdown", "-r", "now", "bye"]);
+    }
+
+    #[test]
+    fn delays_too_long_are_rejected() {
+        let error = shutdown_in(Duration::MAX).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+        assert!(time_in(Duration::from_secs(60)).is_ok());
+    }
+
+    #[test]
+    fn cancel_result_only_falls_back_without_a_reply() {
+        let mut attempts = Vec::new();
+        let outcome = cancel_result(Some(true), &mut attempts).unwrap().unwrap();
+        assert_eq!(outcome.backend, LOGIN1.destination);
+        assert!(cancel_result(None, &mut attempts).is_none());
+        let error = cancel_result(Some(false), &mut attempts)
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::Other);
+        assert_eq!(error.attempts().len(), 1);
+        assert!(attempts.is_empty());
+    }
+
+    #[test]
+    fn save_wall_message_keeps_the_original_message() {
+        save_wall_message(Some(("original".to_string(), false)));
+        save_wall_message(Some(("first".to_string(), true)));
+        assert_eq!(
+            take_saved_wall_message(),
+            Some(("original".to_string(), false))
+        );
+        assert_eq!(take_saved_wall_message(), None);
+    }
+
+    #[test]
+    fn from_property_ignores_an_empty_kind() {
+        assert_eq!(ScheduledShutdown::from_property((String::new(), 0)), None);
+        let scheduled = ScheduledShutdown::from_property(("reboot".to_string(), 5_000_000));
+        assert_eq!(
+            scheduled,
+            Some(ScheduledShutdown {
+                kind: "reboot".to_string(),
+                time: UNIX_EPOCH + Duration::from_secs(5),
+            })
+        );
+    }
+}
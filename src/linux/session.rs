@@ -0,0 +1,264 @@
+//! The sessions and users known to logind, and terminating them.
+
+use std::collections::HashMap;
+
+use zbus::blocking::Connection;
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Value};
+
+use super::{Bus, LOGIN1, login1_or_command};
+use crate::{ShutdownError, ShutdownResult};
+
+/// A login session, as returned by [`list_sessions`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct LoginSession {
+    /// The session ID, as accepted by [`logout_session`].
+    pub id: String,
+    /// The user ID of the session owner.
+    pub uid: u32,
+    /// The user name of the session owner.
+    pub user: String,
+    /// The seat the session is attached to, empty for sessions without one (e.g. SSH).
+    pub seat: String,
+    /// The terminal the session runs on, empty if it has none.
+    pub tty: String,
+    /// `"tty"`, `"x11"`, `"wayland"`, `"mir"` or `"unspecified"`.
+    pub kind: String,
+    /// `"user"`, `"greeter"`, `"lock-screen"` or `"background"`, among others.
+    pub class: String,
+    /// Whether the session was opened remotely.
+    pub remote: bool,
+    /// Whether the session is idle.
+    pub idle: bool,
+}
+
+/// A user with processes running on the machine, as returned by [`list_users`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct LoginUser {
+    /// The user ID, as accepted by [`logout_user`].
+    pub uid: u32,
+    /// The user name.
+    pub name: String,
+    /// `"offline"`, `"lingering"`, `"online"`, `"active"` or `"closing"`.
+    pub state: String,
+    /// Whether all the sessions of the user are idle.
+    pub idle: bool,
+    /// The IDs of the sessions of the user.
+    pub sessions: Vec<String>,
+}
+
+/// An entry of the reply to org.freedesktop.login1.Manager.ListSessions().
+pub(super) type SessionEntry = (String, u32, String, String, OwnedObjectPath);
+
+/// An entry of the reply to org.freedesktop.login1.Manager.ListUsers().
+pub(super) type UserEntry = (u32, String, OwnedObjectPath);
+
+pub(super) const SESSION_INTERFACE: &str = "org.freedesktop.login1.Session";
+pub(super) const USER_INTERFACE: &str = "org.freedesktop.login1.User";
+pub(super) const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+
+impl LoginSession {
+    /// Builds the session from its `ListSessions` entry and its D-BUS properties.
+    pub(super) fn new(
+        (id, uid, user, seat, _): SessionEntry,
+        properties: &HashMap<String, OwnedValue>,
+    ) -> Self {
+        LoginSession {
+            id,
+            uid,
+            user,
+            seat,
+            tty: text(properties, "TTY"),
+            kind: text(properties, "Type"),
+            class: text(properties, "Class"),
+            remote: flag(properties, "Remote"),
+            idle: flag(properties, "IdleHint"),
+        }
+    }
+}
+
+impl LoginUser {
+    /// Builds the user from its `ListUsers` entry and its D-BUS properties.
+    pub(super) fn new((uid, name, _): UserEntry, properties: &HashMap<String, OwnedValue>) -> Self {
+        let sessions = properties
+            .get("Sessions")
+            .and_then(|value| {
+                <Vec<(String, OwnedObjectPath)>>::try_from(Value::try_from(value).ok()?).ok()
+            })
+            .unwrap_or_default();
+        LoginUser {
+            uid,
+            name,
+            state: text(properties, "State"),
+            idle: flag(properties, "IdleHint"),
+            sessions: sessions.into_iter().map(|(id, _)| id).collect(),
+        }
+    }
+}
+
+/// Reads the properties `interface` has on the logind object at `path`. Returns no properties if
+/// there is no connection or if the object is gone, e.g. if the session was closed after being
+/// listed.
+fn properties(
+    conn: Option<&Connection>,
+    path: &OwnedObjectPath,
+    interface: &str,
+) -> HashMap<String, OwnedValue> {
+    let Some(conn) = conn else {
+        return HashMap::new();
+    };
+    let dest = LOGIN1.destination;
+    conn.call_method(
+        Some(dest),
+        path,
+        Some(PROPERTIES_INTERFACE),
+        "GetAll",
+        &(interface),
+    )
+    .and_then(|reply| reply.body().deserialize())
+    .unwrap_or_default()
+}
+
+fn text(properties: &HashMap<String, OwnedValue>, name: &str) -> String {
+    properties
+        .get(name)
+        .and_then(|value| <&str>::try_from(&**value).ok())
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn flag(properties: &HashMap<String, OwnedValue>, name: &str) -> bool {
+    properties
+        .get(name)
+        .and_then(|value| bool::try_from(&**value).ok())
+        .unwrap_or_default()
+}
+
+/// Linux specific function listing the login sessions.
+/// The following D-BUS calls are attempted:
+/// - org.freedesktop.login1.Manager.ListSessions()
+/// - org.freedesktop.DBus.Properties.GetAll("org.freedesktop.login1.Session"), for each session
+pub fn list_sessions() -> ShutdownResult<Vec<LoginSession>> {
+    let mut attempts = Vec::new();
+    let reply = LOGIN1.request::<_, Vec<SessionEntry>>(&mut attempts, "ListSessions", &());
+    let Some(sessions) = reply else {
+        return Err(ShutdownError::from_attempts(attempts));
+    };
+    let conn = Bus::System.connect().ok();
+    Ok(sessions
+        .into_iter()
+        .map(|session| {
+            let properties = properties(conn.as_ref(), &session.4, SESSION_INTERFACE);
+            LoginSession::new(session, &properties)
+        })
+        .collect())
+}
+
+/// Linux specific function listing the users with processes running on the machine.
+/// The following D-BUS calls are attempted:
+/// - org.freedesktop.login1.Manager.ListUsers()
+/// - org.freedesktop.DBus.Properties.GetAll("org.freedesktop.login1.User"), for each user
+pub fn list_users() -> ShutdownResult<Vec<LoginUser>> {
+    let mut attempts = Vec::new();
+    let reply = LOGIN1.request::<_, Vec<UserEntry>>(&mut attempts, "ListUsers", &());
+    let Some(users) = reply else {
+        return Err(ShutdownError::from_attempts(attempts));
+    };
+    let conn = Bus::System.connect().ok();
+    Ok(users
+        .into_iter()
+        .map(|user| {
+            let properties = properties(conn.as_ref(), &user.2, USER_INTERFACE);
+            LoginUser::new(user, &properties)
+        })
+        .collect())
+}
+
+/// Linux specific function to end the session `id`, which may belong to another user. See
+/// [`list_sessions`] for the session IDs.
+/// The following D-BUS call is attempted:
+/// - org.freedesktop.login1.Manager.TerminateSession(id)
+///
+/// If it fails, as a last resort this function calls `loginctl terminate-session id`.
+pub fn logout_session(id: &str) -> ShutdownResult {
+    login1_or_command(
+        "TerminateSession",
+        &(id),
+        &["loginctl", "terminate-session", id],
+    )
+}
+
+/// Linux specific function to end all the sessions of the user `uid`, and kill their processes.
+/// The following D-BUS call is attempted:
+/// - org.freedesktop.login1.Manager.TerminateUser(uid)
+///
+/// If it fails, as a last resort this function calls `loginctl terminate-user uid`.
+pub fn logout_user(uid: u32) -> ShutdownResult {
+    login1_or_command(
+        "TerminateUser",
+        &(uid),
+        &["loginctl", "terminate-user", &uid.to_string()],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(path: &str) -> OwnedObjectPath {
+        OwnedObjectPath::try_from(path).unwrap()
+    }
+
+    fn owned(value: Value<'_>) -> OwnedValue {
+        value.try_into().unwrap()
+    }
+
+    #[test]
+    fn session_reads_its_properties() {
+        let entry = (
+            "c1".to_string(),
+            1000,
+            "user".to_string(),
+            "seat0".to_string(),
+            path("/org/freedesktop/login1/session/c1"),
+        );
+        let properties = HashMap::from([
+            ("TTY".to_string(), owned(Value::from("tty2"))),
+            ("Type".to_string(), owned(Value::from("wayland"))),
+            ("Remote".to_string(), owned(Value::from(true))),
+            // wrong type, read as the default value
+            ("IdleHint".to_string(), owned(Value::from("yes"))),
+        ]);
+        let session = LoginSession::new(entry, &properties);
+        assert_eq!(session.id, "c1");
+        assert_eq!(session.seat, "seat0");
+        assert_eq!(session.tty, "tty2");
+        assert_eq!(session.kind, "wayland");
+        assert_eq!(session.class, "");
+        assert!(session.remote);
+        assert!(!session.idle);
+    }
+
+    #[test]
+    fn user_lists_the_ids_of_its_sessions() {
+        let entry = (
+            1000,
+            "user".to_string(),
+            path("/org/freedesktop/login1/user/_1000"),
+        );
+        let sessions = vec![
+            ("c1", path("/org/freedesktop/login1/session/c1")),
+            ("3", path("/org/freedesktop/login1/session/_33")),
+        ];
+        let properties = HashMap::from([
+            ("State".to_string(), owned(Value::from("active"))),
+            ("Sessions".to_string(), owned(Value::from(sessions))),
+        ]);
+        let user = LoginUser::new(entry, &properties);
+        assert_eq!(user.uid, 1000);
+        assert_eq!(user.state, "active");
+        assert_eq!(user.sessions, ["c1", "3"]);
+        assert!(!user.idle);
+    }
+}
@@ -0,0 +1,272 @@
+//! The magic SysRq key.
+//!
+//! Reference: <https://www.kernel.org/doc/html/latest/admin-guide/sysrq.html>
+
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use crate::{Attempt, Invocation, Outcome, ShutdownError, ShutdownResult, Status};
+
+/// Returns the bit of `kernel.sysrq` enabling the SysRq `command`.
+fn function_bit(command: u8) -> u32 {
+    match command {
+        // keyboard control
+        b'r' => 4,
+        // sync
+        b's' => 16,
+        // remount read-only
+        b'u' => 32,
+        // signalling of processes
+        b'e' | b'i' => 64,
+        // reboot and power off
+        b'b' | b'o' => 128,
+        // all functions
+        _ => 1,
+    }
+}
+
+/// The SysRq files of a `/proc` filesystem.
+pub(crate) struct SysRq {
+    /// `kernel.sysrq`, the bitmask of the enabled SysRq functions.
+    mask: PathBuf,
+    trigger: PathBuf,
+}
+
+impl SysRq {
+    /// The SysRq files of the running kernel.
+    pub(crate) fn new() -> Self {
+        SysRq::with_proc_root("/proc")
+    }
+
+    /// The SysRq files of the `/proc` filesystem mounted at `root`.
+    pub(crate) fn with_proc_root(root: impl Into<PathBuf>) -> Self {
+        let root = root.into();
+        SysRq {
+            mask: root.join("sys/kernel/sysrq"),
+            trigger: root.join("sysrq-trigger"),
+        }
+    }
+
+    fn read_mask(&self) -> io::Result<u32> {
+        fs::read_to_string(&self.mask)?
+            .trim()
+            .parse()
+            .map_err(|error| io::Error::new(ErrorKind::InvalidData, error))
+    }
+
+    fn write_mask(&self, mask: u32) -> io::Result<()> {
+        fs::write(&self.mask, mask.to_string())
+    }
+
+    fn trigger(&self, command: u8) -> io::Result<()> {
+        fs::write(&self.trigger, [command])
+    }
+
+    /// Enables the functions `commands` need on top of the current ones and calls `f`, restoring
+    /// the original `kernel.sysrq` value if it fails.
+    pub(crate) fn with_enabled<T>(
+        &self,
+        commands: &[u8],
+        f: impl FnOnce(&Self) -> ShutdownResult<T>,
+    ) -> ShutdownResult<T> {
+        let original = self.read_mask().map_err(|error| self.failure(error))?;
+        let needed = commands.iter().fold(0, |mask, &c| mask | function_bit(c));
+        // 1 - all functions are enabled already
+        let changed = original != 1 && original & needed != needed;
+        if changed {
+            self.write_mask(original | needed)
+                .map_err(|error| self.failure(error))?;
+        }
+        let result = f(self);
+        if result.is_err() && changed {
+            let _ = self.write_mask(original);
+        }
+        result
+    }
+
+    /// Triggers the SysRq `command`, enabling its function first if needed.
+    pub(crate) fn perform(&self, command: u8) -> ShutdownResult {
+        self.with_enabled(&[command], |sysrq| {
+            sysrq
+                .trigger(command)
+                .map_err(|error| step_failure(command, error))?;
+            Ok(sysrq.outcome())
+        })
+    }
+
+    fn outcome(&self) -> Outcome {
+        let trigger = self.trigger.to_string_lossy().into_owned();
+        Outcome::new("sysrq", Invocation::Native(trigger), Status::Completed)
+    }
+
+    fn failure(&self, error: io::Error) -> ShutdownError {
+        let mask = self.mask.to_string_lossy();
+        ShutdownError::from_attempts(vec![Attempt::new(mask, error.to_string(), error.kind())])
+    }
+}
+
+/// Sends `signal` to every process except init and the current one.
+fn signal_all(signal: libc::c_int) -> io::Result<()> {
+    super::kill(-1, signal)
+}
+
+/// The R-E-I-S-U-B emergency reboot sequence, waiting between each step so that processes can
+/// exit and buffers reach the disks:
+/// - `r` takes the keyboard out of raw mode
+/// - `e` sends `SIGTERM` to all processes
+/// - `i` sends `SIGKILL` to all processes
+/// - `s` syncs all mounted filesystems
+/// - `u` remounts all mounted filesystems read-only
+/// - `b` reboots the machine
+///
+/// The SysRq `e` and `i` functions would also kill the current process before the sequence ends,
+/// so these two steps signal every other process with `kill(-1, ...)` instead.
+///
+/// Only the `kernel.sysrq` functions the sequence needs are enabled, and its original value is
+/// restored if a step fails.
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use system_shutdown::EmergencyReboot;
+///
+/// let mut reboot = EmergencyReboot::new();
+/// reboot.after_terminate = Duration::from_secs(10);
+/// if let Err(error) = reboot.perform() {
+///     eprintln!("Failed to reboot: {error}");
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct EmergencyReboot {
+    /// The time to wait after taking the keyboard out of raw mode. Defaults to 1 second.
+    pub after_unraw: Duration,
+    /// The time processes have to exit after `SIGTERM`. Defaults to 5 seconds.
+    pub after_terminate: Duration,
+    /// The time to wait after `SIGKILL`. Defaults to 2 seconds.
+    pub after_kill: Duration,
+    /// The time the filesystems have to sync. Defaults to 5 seconds.
+    pub after_sync: Duration,
+    /// The time the filesystems have to be remounted read-only. Defaults to 2 seconds.
+    pub after_remount: Duration,
+}
+
+impl Default for EmergencyReboot {
+    fn default() -> Self {
+        EmergencyReboot {
+            after_unraw: Duration::from_secs(1),
+            after_terminate: Duration::from_secs(5),
+            after_kill: Duration::from_secs(2),
+            after_sync: Duration::from_secs(5),
+            after_remount: Duration::from_secs(2),
+        }
+    }
+}
+
+impl EmergencyReboot {
+    /// Creates the sequence with the default waits.
+    pub fn new() -> Self {
+        EmergencyReboot::default()
+    }
+
+    /// Runs the sequence. It does not return on success, as the machine reboots.
+    pub fn perform(&self) -> ShutdownResult {
+        SysRq::new().with_enabled(b"rsub", |sysrq| {
+            self.run(sysrq)?;
+            Ok(sysrq.outcome())
+        })
+    }
+
+    fn run(&self, sysrq: &SysRq) -> ShutdownResult<()> {
+        let step = |command: u8, result: io::Result<()>, wait: Duration| {
+            result.map_err(|error| step_failure(command, error))?;
+            thread::sleep(wait);
+            Ok(())
+        };
+        step(b'r', sysrq.trigger(b'r'), self.after_unraw)?;
+        step(b'e', signal_all(libc::SIGTERM), self.after_terminate)?;
+        step(b'i', signal_all(libc::SIGKILL), self.after_kill)?;
+        step(b's', sysrq.trigger(b's'), self.after_sync)?;
+        step(b'u', sysrq.trigger(b'u'), self.after_remount)?;
+        step(b'b', sysrq.trigger(b'b'), Duration::ZERO)
+    }
+}
+
+fn step_failure(command: u8, error: io::Error) -> ShutdownError {
+    let backend = format!("sysrq {}", char::from(command));
+    ShutdownError::from_attempts(vec![Attempt::new(backend, error.to_string(), error.kind())])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fake `/proc` filesystem holding the SysRq files, removed on drop.
+    struct Proc(PathBuf);
+
+    impl Proc {
+        fn new(name: &str, mask: &str) -> Self {
+            let root =
+                std::env::temp_dir().join(format!("system_shutdown-{}-{name}", std::process::id()));
+            let _ = fs::remove_dir_all(&root);
+            fs::create_dir_all(root.join("sys/kernel")).unwrap();
+            fs::write(root.join("sys/kernel/sysrq"), mask).unwrap();
+            fs::write(root.join("sysrq-trigger"), "").unwrap();
+            Proc(root)
+        }
+
+        fn sysrq(&self) -> SysRq {
+            SysRq::with_proc_root(&self.0)
+        }
+
+        fn mask(&self) -> String {
+            fs::read_to_string(self.0.join("sys/kernel/sysrq")).unwrap()
+        }
+    }
+
+    impl Drop for Proc {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn with_enabled_adds_only_the_needed_functions() {
+        let proc = Proc::new("needed", "176\n");
+        let mask = proc
+            .sysrq()
+            .with_enabled(b"rsub", |_| Ok(proc.mask()))
+            .unwrap();
+        // 176 enables sync, remount and reboot already, so only keyboard control is added
+        assert_eq!(mask, "180");
+    }
+
+    #[test]
+    fn with_enabled_keeps_a_mask_enabling_all_functions() {
+        let proc = Proc::new("all", "1\n");
+        proc.sysrq().with_enabled(b"rsub", |_| Ok(())).unwrap();
+        assert_eq!(proc.mask(), "1\n");
+    }
+
+    #[test]
+    fn perform_restores_the_mask_if_the_trigger_fails() {
+        let proc = Proc::new("restore", "16\n");
+        // writing to a directory fails
+        fs::remove_file(proc.0.join("sysrq-trigger")).unwrap();
+        fs::create_dir(proc.0.join("sysrq-trigger")).unwrap();
+        let error = proc.sysrq().perform(b'b').unwrap_err();
+        assert_eq!(error.attempts()[0].backend, "sysrq b");
+        assert_eq!(proc.mask(), "16");
+    }
+
+    #[test]
+    fn perform_writes_the_command_to_the_trigger() {
+        let proc = Proc::new("trigger", "0\n");
+        let outcome = proc.sysrq().perform(b'o').unwrap();
+        assert_eq!(outcome.backend, "sysrq");
+        assert_eq!(fs::read(proc.0.join("sysrq-trigger")).unwrap(), b"o");
+        assert_eq!(proc.mask(), "128");
+    }
+}
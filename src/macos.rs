@@ -1,84 +1,174 @@
-use std::io::{Error, ErrorKind};
+use std::io::ErrorKind;
 use std::process::Command;
 
-use super::not_implemented;
 use super::ShutdownResult;
+use super::not_implemented;
+use super::{
+    Action, Attempt, Capabilities, Capability, Invocation, Outcome, ShutdownError, Status, Step,
+};
 
-fn invoke_script(script: &str) -> ShutdownResult {
+const SHUT_DOWN_SCRIPT: &str = "tell application \"System Events\" to shut down";
+const RESTART_SCRIPT: &str = "tell application \"System Events\" to restart";
+const LOG_OUT_SCRIPT: &str = "tell application \"System Events\" to log out";
+const SLEEP_SCRIPT: &str = "tell application \"System Events\" to sleep";
+
+fn script_invocation(script: &str) -> Invocation {
+    Invocation::Command(format!("osascript -e '{}'", script))
+}
+
+fn invoke_script(script: &str, status: Status) -> ShutdownResult {
     let mut cmd = Command::new("osascript");
     cmd.args(&["-e", script]);
     match cmd.output() {
         Ok(output) => {
             if output.status.success() && output.stderr.is_empty() {
-                return Ok(());
+                return Ok(Outcome::new("osascript", script_invocation(script), status));
             }
-            Err(Error::new(
+            Err(ShutdownError::CommandFailed(vec![Attempt::new(
+                "osascript",
+                String::from_utf8_lossy(&output.stderr),
                 ErrorKind::Other,
-                String::from_utf8_lossy(&output.stderr).into_owned(),
-            ))
+            )]))
         }
-        Err(error) => Err(error),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// macOS specific function listing the AppleScript run for each action. Hibernation is not supported.
+pub fn plan(action: Action) -> Vec<Step> {
+    let script = match action {
+        Action::Shutdown => SHUT_DOWN_SCRIPT,
+        Action::Reboot => RESTART_SCRIPT,
+        Action::Logout => LOG_OUT_SCRIPT,
+        Action::Sleep => SLEEP_SCRIPT,
+        _ => return Vec::new(),
+    };
+    vec![Step {
+        backend: "osascript".to_string(),
+        available: true,
+        invocations: vec![script_invocation(script)],
+    }]
+}
+
+/// macOS specific function reporting which power actions this crate can perform. Hibernation is not supported.
+pub fn capabilities() -> Capabilities {
+    Capabilities {
+        shutdown: Capability::Yes,
+        reboot: Capability::Yes,
+        suspend: Capability::Yes,
+        hibernate: Capability::NotApplicable,
+        hybrid_sleep: Capability::NotApplicable,
+        suspend_then_hibernate: Capability::NotApplicable,
+        kexec_reboot: Capability::NotApplicable,
     }
 }
 
+#[doc(hidden)]
+pub fn can_shutdown() -> Capability {
+    capabilities().shutdown
+}
+
+#[doc(hidden)]
+pub fn can_reboot() -> Capability {
+    capabilities().reboot
+}
+
+#[doc(hidden)]
+pub fn can_suspend() -> Capability {
+    capabilities().suspend
+}
+
+#[doc(hidden)]
+pub fn can_hibernate() -> Capability {
+    capabilities().hibernate
+}
+
 /// macOS requires to explicitly allow the application to call "System Events". If you want to use this crate in an unattended way (automation etc.),
 /// you may want to ask for permission beforehand to allow this app to call "System Events".
 /// This function requests the "System Events" to "stop current screen saver", which should be a fairly safe operation.
 pub fn request_permission_dialog() -> ShutdownResult {
-    invoke_script("tell application \"System Events\" to stop current screen saver")
+    invoke_script(
+        "tell application \"System Events\" to stop current screen saver",
+        Status::Completed,
+    )
 }
 
 /// macOS specific function to shut down the system using AppleScript and "System Events" call "shut down"
 /// First time you use this, macOS will ask for a permission. If you want to ask for a permission beforehand, use [`request_permission_dialog`]
 pub fn shutdown() -> ShutdownResult {
-    invoke_script("tell application \"System Events\" to shut down")
+    invoke_script(SHUT_DOWN_SCRIPT, Status::Completed)
 }
 
 /// macOS specific function to force shut down the system using `shutdown -h now`.
 pub fn force_shutdown() -> ShutdownResult {
     let output = Command::new("shutdown").args(["-h", "now"]).output()?;
     if output.status.success() {
-        return Ok(());
+        let invocation = Invocation::Command("shutdown -h now".to_string());
+        return Ok(Outcome::new("command", invocation, Status::Completed));
     }
-    Err(Error::new(
+    Err(ShutdownError::CommandFailed(vec![Attempt::new(
+        "shutdown -h now",
+        String::from_utf8_lossy(&output.stderr),
         ErrorKind::Other,
-        String::from_utf8_lossy(&output.stderr).into_owned(),
-    ))
+    )]))
 }
 
 /// macOS specific function to reboot using AppleScript and "System Events" call "restart"
 /// First time you use this, macOS will ask for a permission. If you want to ask for a permission beforehand, use [`request_permission_dialog`]
 pub fn reboot() -> ShutdownResult {
-    invoke_script("tell application \"System Events\" to restart")
+    invoke_script(RESTART_SCRIPT, Status::Completed)
 }
 
 /// macOS specific function to force reboot the system using `shutdown -r now`.
 pub fn force_reboot() -> ShutdownResult {
     let output = Command::new("shutdown").args(["-r", "now"]).output()?;
     if output.status.success() {
-        return Ok(());
+        let invocation = Invocation::Command("shutdown -r now".to_string());
+        return Ok(Outcome::new("command", invocation, Status::Completed));
     }
-    Err(Error::new(
+    Err(ShutdownError::CommandFailed(vec![Attempt::new(
+        "shutdown -r now",
+        String::from_utf8_lossy(&output.stderr),
         ErrorKind::Other,
-        String::from_utf8_lossy(&output.stderr).into_owned(),
-    ))
+    )]))
 }
 
 /// macOS specific function to logout with a confirmation dialog using AppleScript and "System Events" call "log out".
 /// First time you use this, macOS will ask for a permission. If you want to ask for a permission beforehand, use [`request_permission_dialog`]
 pub fn logout() -> ShutdownResult {
-    invoke_script("tell application \"System Events\" to log out")
+    invoke_script(LOG_OUT_SCRIPT, Status::Interactive)
 }
 
 /// macOS specific function to force logout without showing a confirmation dialog using AppleScript and "loginwindow" call "«event aevtrlgo»"
 pub fn force_logout() -> ShutdownResult {
-    invoke_script("tell application \"loginwindow\" to «event aevtrlgo»")
+    invoke_script(
+        "tell application \"loginwindow\" to «event aevtrlgo»",
+        Status::Completed,
+    )
 }
 
 /// macOS specific function to put the machine to sleep using AppleScript and "System Events" call "sleep"
 /// First time you use this, macOS will ask for a permission. If you want to ask for a permission beforehand, use [`request_permission_dialog`]
 pub fn sleep() -> ShutdownResult {
-    invoke_script("tell application \"System Events\" to sleep")
+    invoke_script(SLEEP_SCRIPT, Status::Completed)
+}
+
+#[doc(hidden)]
+pub fn shutdown_with_message(
+    _message: &str,
+    _timeout: u32,
+    _force_close_apps: bool,
+) -> ShutdownResult {
+    not_implemented!()
+}
+
+#[doc(hidden)]
+pub fn reboot_with_message(
+    _message: &str,
+    _timeout: u32,
+    _force_close_apps: bool,
+) -> ShutdownResult {
+    not_implemented!()
 }
 
 #[doc(hidden)]
@@ -86,3 +176,33 @@ pub fn hibernate() -> ShutdownResult {
     // It's possible but not generally a good idea https://superuser.com/a/630985
     not_implemented!()
 }
+
+#[doc(hidden)]
+pub fn hybrid_sleep() -> ShutdownResult {
+    not_implemented!()
+}
+
+#[doc(hidden)]
+pub fn suspend_then_hibernate() -> ShutdownResult {
+    not_implemented!()
+}
+
+#[doc(hidden)]
+pub fn lock_screen() -> ShutdownResult {
+    not_implemented!()
+}
+
+#[doc(hidden)]
+pub fn halt() -> ShutdownResult {
+    not_implemented!()
+}
+
+#[doc(hidden)]
+pub fn kexec_reboot() -> ShutdownResult {
+    not_implemented!()
+}
+
+#[doc(hidden)]
+pub fn soft_reboot() -> ShutdownResult {
+    not_implemented!()
+}
@@ -0,0 +1,83 @@
+use std::fmt;
+
+/// How a backend was invoked to perform a power action.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Invocation {
+    /// A D-BUS method call, e.g. `org.freedesktop.login1.Manager.PowerOff` on `org.freedesktop.login1`.
+    DBus {
+        /// The bus name the call was sent to.
+        destination: String,
+        /// The fully qualified method name, including its interface.
+        method: String,
+    },
+    /// A command line, e.g. `shutdown -h now`.
+    Command(String),
+    /// A native OS interface, e.g. `ExitWindowsEx` or `/proc/sysrq-trigger`.
+    Native(String),
+}
+
+impl fmt::Display for Invocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Invocation::DBus {
+                destination,
+                method,
+            } => write!(f, "{method} on {destination}"),
+            Invocation::Command(command) | Invocation::Native(command) => f.write_str(command),
+        }
+    }
+}
+
+/// The state a power action was left in by the backend that accepted it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Status {
+    /// The action was carried out, or is about to be.
+    Completed,
+    /// The request was handed to an interactive dialog where the user can confirm it.
+    Interactive,
+}
+
+/// Describes which backend performed a power action, and how.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Outcome {
+    /// The backend that accepted the request, e.g. `org.gnome.SessionManager` or `command`.
+    pub backend: String,
+    /// How the backend was invoked.
+    pub invocation: Invocation,
+    /// The state the action was left in.
+    pub status: Status,
+}
+
+impl Outcome {
+    /// Creates a new outcome.
+    pub fn new(backend: impl Into<String>, invocation: Invocation, status: Status) -> Self {
+        Outcome {
+            backend: backend.into(),
+            invocation,
+            status,
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} via {}", self.backend, self.invocation)?;
+        if self.status == Status::Interactive {
+            f.write_str(" (waiting for confirmation)")?;
+        }
+        Ok(())
+    }
+}
+
+/// A backend that would be tried to perform a power action, as returned by [`crate::plan`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Step {
+    /// The backend name, e.g. `org.freedesktop.login1` or `command`.
+    pub backend: String,
+    /// Whether the backend is available on this system. Unavailable backends are skipped.
+    pub available: bool,
+    /// The calls the backend would make, in order.
+    pub invocations: Vec<Invocation>,
+}
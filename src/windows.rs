@@ -9,11 +9,11 @@ use windows::{
             TOKEN_ADJUST_PRIVILEGES, TOKEN_PRIVILEGES, TOKEN_QUERY,
         },
         System::{
-            Power::SetSuspendState,
+            Power::{IsPwrHibernateAllowed, IsPwrSuspendAllowed, SetSuspendState},
             Shutdown::{
                 ExitWindowsEx, InitiateSystemShutdownW, EWX_LOGOFF, EWX_REBOOT, EWX_SHUTDOWN,
                 EXIT_WINDOWS_FLAGS, SHTDN_REASON_FLAG_PLANNED, SHTDN_REASON_MAJOR_OPERATINGSYSTEM,
-                SHTDN_REASON_MINOR_UPGRADE, EWX_FORCE, EWX_FORCEIFHUNG
+                SHTDN_REASON_MINOR_UPGRADE, EWX_FORCE, EWX_FORCEIFHUNG, LockWorkStation
             },
             Threading::{GetCurrentProcess, OpenProcessToken},
         }
@@ -21,16 +21,18 @@ use windows::{
 };
 
 use super::ShutdownResult;
+use super::not_implemented;
+use super::{Action, Capabilities, Capability, Invocation, Outcome, Status, Step};
 
 #[doc(hidden)]
 #[macro_export]
 macro_rules! last_os_error {
     () => {
-        Err(Error::last_os_error())
+        Err(Error::last_os_error().into())
     };
 }
 
-fn request_privileges() -> ShutdownResult {
+fn request_privileges() -> ShutdownResult<()> {
     unsafe {
         let mut token: HANDLE = HANDLE::default();
         let mut tkp: TOKEN_PRIVILEGES = mem::zeroed();
@@ -61,6 +63,14 @@ fn request_privileges() -> ShutdownResult {
     Ok(())
 }
 
+fn native_outcome(function: &str) -> Outcome {
+    Outcome::new(
+        function,
+        Invocation::Native(function.to_string()),
+        Status::Completed,
+    )
+}
+
 fn exit_windows(flag: u32) -> ShutdownResult {
     unsafe {
         request_privileges()?;
@@ -75,7 +85,7 @@ fn exit_windows(flag: u32) -> ShutdownResult {
             return last_os_error!();
         }
     }
-    Ok(())
+    Ok(native_outcome("ExitWindowsEx"))
 }
 fn initiate_system_shutdown(
     message: &str,
@@ -97,7 +107,7 @@ fn initiate_system_shutdown(
             return last_os_error!();
         }
     }
-    Ok(())
+    Ok(native_outcome("InitiateSystemShutdownW"))
 }
 fn set_suspend_state(hibernate: bool) -> ShutdownResult {
     unsafe {
@@ -106,7 +116,57 @@ fn set_suspend_state(hibernate: bool) -> ShutdownResult {
             return last_os_error!();
         }
     }
-    Ok(())
+    Ok(native_outcome("SetSuspendState"))
+}
+
+/// Windows specific function listing the API call used for each action.
+pub fn plan(action: Action) -> Vec<Step> {
+    let function = match action {
+        Action::Shutdown | Action::Reboot | Action::Logout => "ExitWindowsEx",
+        Action::Sleep | Action::Hibernate => "SetSuspendState",
+        Action::LockScreen => "LockWorkStation",
+        _ => return Vec::new(),
+    };
+    vec![Step {
+        backend: function.to_string(),
+        available: true,
+        invocations: vec![Invocation::Native(function.to_string())],
+    }]
+}
+
+/// Windows specific function querying which power actions are allowed, using the `IsPwrSuspendAllowed()`
+/// and `IsPwrHibernateAllowed()` API calls.
+pub fn capabilities() -> Capabilities {
+    let (suspend, hibernate) = unsafe { (IsPwrSuspendAllowed(), IsPwrHibernateAllowed()) };
+    Capabilities {
+        shutdown: Capability::Yes,
+        reboot: Capability::Yes,
+        suspend: Capability::from(suspend),
+        hibernate: Capability::from(hibernate),
+        hybrid_sleep: Capability::NotApplicable,
+        suspend_then_hibernate: Capability::NotApplicable,
+        kexec_reboot: Capability::NotApplicable,
+    }
+}
+
+#[doc(hidden)]
+pub fn can_shutdown() -> Capability {
+    capabilities().shutdown
+}
+
+#[doc(hidden)]
+pub fn can_reboot() -> Capability {
+    capabilities().reboot
+}
+
+#[doc(hidden)]
+pub fn can_suspend() -> Capability {
+    capabilities().suspend
+}
+
+#[doc(hidden)]
+pub fn can_hibernate() -> Capability {
+    capabilities().hibernate
 }
 
 /// Windows specific function to gracefully request system shutdown, providing a way to show a message,
@@ -164,3 +224,38 @@ pub fn sleep() -> ShutdownResult {
 pub fn hibernate() -> ShutdownResult {
     set_suspend_state(true)
 }
+
+/// Windows specific function to lock the workstation using `LockWorkStation()` API call.
+pub fn lock_screen() -> ShutdownResult {
+    unsafe {
+        if !LockWorkStation().is_ok() {
+            return last_os_error!();
+        }
+    }
+    Ok(native_outcome("LockWorkStation"))
+}
+
+#[doc(hidden)]
+pub fn hybrid_sleep() -> ShutdownResult {
+    not_implemented!()
+}
+
+#[doc(hidden)]
+pub fn suspend_then_hibernate() -> ShutdownResult {
+    not_implemented!()
+}
+
+#[doc(hidden)]
+pub fn halt() -> ShutdownResult {
+    not_implemented!()
+}
+
+#[doc(hidden)]
+pub fn kexec_reboot() -> ShutdownResult {
+    not_implemented!()
+}
+
+#[doc(hidden)]
+pub fn soft_reboot() -> ShutdownResult {
+    not_implemented!()
+}